@@ -1,4 +1,4 @@
-use crate::{util, loader};
+use crate::{util, loader, watch::FileWatcher};
 use vulkano::{buffer::{BufferUsage, CpuAccessibleBuffer}, command_buffer::CommandBuffer, device::{Device, DeviceOwned, Queue}, image::{ImageCreateFlags, ImageDimensions}};
 use vulkano::instance::{Instance, ApplicationInfo};
 use vulkano::image::{StorageImage, ImageUsage, ImageAccess};
@@ -22,9 +22,10 @@ use winit::event_loop::{EventLoop, ControlFlow};
 use winit::event::{Event, WindowEvent, DeviceEvent, ElementState};
 use winit::dpi::PhysicalSize;
 
-use std::{future::Ready, hash::Hash};
-use std::sync::Arc;
-use std::time::Instant;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use std::fmt::Debug;
 use std::fs;
 use std::path;
@@ -48,8 +49,15 @@ pub struct Canvas<Ds, Update, Resize, Pc: 'static, DsBuilder> where
 	pub queue: Arc<Queue>,
 	pub window_size: PhysicalSize<u32>,
 	pub shader: Option<loader::Shader>,
+	pub capture_dir: path::PathBuf, // Folder in which the recordings are saved
+	cursor_grab: Arc<AtomicBool>, // The cursor is grabbed and hidden while it's true
+	recording: Arc<AtomicBool>, // The frames are saved while it's true, F2 toggles it
+	shader_rebuild: Arc<Mutex<Option<ShaderRebuild>>>, // Taken by the event loop to compile the shader with other defines
 }
 
+/// Layout and defines to compile the shader with, replacing the ones it was loaded with
+pub type ShaderRebuild = (loader::MainLayout, Vec<(&'static str, String)>);
+
 impl<Ds: 'static, Update: 'static, Resize: 'static, Pc, DsBuilder: 'static> Canvas<Ds, Update, Resize, Pc, DsBuilder> where 
 	Ds: DescriptorSet + DescriptorSetDesc + DeviceOwned + Eq + Hash + PartialEq + Send + Sync,
 	Update: FnMut(Option<&winit::event::Event<()>>, f64) -> (Pc, bool),
@@ -79,8 +87,10 @@ impl<Ds: 'static, Update: 'static, Resize: 'static, Pc, DsBuilder: 'static> Canv
 	/// - `Option<winit::event::Event<()>>` The same event as the one passed in
 	/// - `Pc` push_constant implementing the `SpecializationConstants` trait
 	/// - `bool` Indicates whether the ds_builder needs to be recalled or not
-	pub fn new(window_size: PhysicalSize<u32>, ds_builder: DsBuilder, app_info: &ApplicationInfo) -> Self {
-		let (instance, device, queue) = util::init_vulkano(app_info);
+	/// #### device_index
+	/// Index of the physical device to use, see `util::list_devices`, the first device is used when `None`
+	pub fn new(window_size: PhysicalSize<u32>, ds_builder: DsBuilder, app_info: &ApplicationInfo, device_index: Option<usize>) -> Self {
+		let (instance, device, queue) = util::init_vulkano(app_info, device_index);
 
 		Self {
 			ds_builder,
@@ -89,6 +99,10 @@ impl<Ds: 'static, Update: 'static, Resize: 'static, Pc, DsBuilder: 'static> Canv
 			queue,
 			window_size,
 			shader: None,
+			capture_dir: path::PathBuf::from("Captures"),
+			cursor_grab: Arc::new(AtomicBool::new(false)),
+			recording: Arc::new(AtomicBool::new(false)),
+			shader_rebuild: Arc::new(Mutex::new(None)),
 		}
 	}
 
@@ -96,10 +110,30 @@ impl<Ds: 'static, Update: 'static, Resize: 'static, Pc, DsBuilder: 'static> Canv
 		self.shader = Some(cs);
 	}
 
+	/// Flag shared with the update closure, the cursor is grabbed and hidden while it's true
+	pub fn set_cursor_grab(&mut self, flag: Arc<AtomicBool>) {
+		self.cursor_grab = flag;
+	}
+
+	/// Flag shared with the update closure, the frames are saved to a new folder of the capture folder while it's true
+	pub fn set_recording(&mut self, flag: Arc<AtomicBool>) {
+		self.recording = flag;
+	}
+
+	/// Shared with the update closure, the shader is compiled again with the layout and defines put in it
+	/// The descriptor set is rebuilt with the new layout, the update closure requesting it should also return true
+	pub fn set_shader_rebuild(&mut self, request: Arc<Mutex<Option<ShaderRebuild>>>) {
+		self.shader_rebuild = request;
+	}
+
+	pub fn set_capture_dir<P: Into<path::PathBuf>>(&mut self, dir: P) {
+		self.capture_dir = dir.into();
+	}
+
 	/// #### Arguments
 	/// - `animation_fps` target fps for animation, mainly defines how `t` passed to `update` closure is incremented
 	pub fn run(self, animation_fps: f64) -> Result<(), String> { // Runs the event_loop
-		let shader = match self.shader {
+		let mut shader = match self.shader {
 			Some(s) => s,
 			None => return Err(String::from("The shader was not set"))
 		};
@@ -147,14 +181,18 @@ impl<Ds: 'static, Update: 'static, Resize: 'static, Pc, DsBuilder: 'static> Canv
 		println!("Created swapchain with {} images using format {:?}", images.len(), swapchain.format());
 
 		// setting up the compute pipeline
-		let compute_pipeline = Arc::new(ComputePipeline::new(
+		let mut compute_pipeline = Arc::new(ComputePipeline::new(
 			device.clone(),
 			&shader.main_entry_point(),
 			&(),
 			None
 		).unwrap());
 
-		let layout = compute_pipeline.layout().descriptor_set_layout(0).unwrap().to_owned();
+		// The shader is compiled again when one of its files changes
+		let mut shader_watcher = FileWatcher::new(shader.sources().iter().cloned());
+		println!("Watching {} shader files", shader.sources().len());
+
+		let mut layout = compute_pipeline.layout().descriptor_set_layout(0).unwrap().to_owned();
 
 		let inner_size = surface.window().inner_size();
 		let (mut descriptor_set, mut output_img, mut dispatch, mut update, resize) = (ds_builder.clone())(inner_size, device.clone(), queue.clone(), layout.clone());
@@ -163,7 +201,6 @@ impl<Ds: 'static, Update: 'static, Resize: 'static, Pc, DsBuilder: 'static> Canv
 		(0 .. inner_size.width * inner_size.height * 4).map(|_| 255u8))
 			.expect("failed to create image_save_buffer");
 
-		let mut dest_image = images[0].clone(); // This arc will reference the image being rendered to every time
 		let mut save_image = StorageImage::with_usage( // Image which is potentially used to save on the disk
 			device.clone(), ImageDimensions::Dim2d { width: inner_size.width, height: inner_size.height, array_layers: 1 }, Format::R8G8B8A8Unorm,
 			ImageUsage {
@@ -175,15 +212,13 @@ impl<Ds: 'static, Update: 'static, Resize: 'static, Pc, DsBuilder: 'static> Canv
 			vec![queue.family()]
 		).unwrap();
 
-		let (mut dest_dim, mut output_dim, scale) = {
-			let _dest_dim = images[0].dimensions().width_height();
+		let (mut dest_dim, mut output_dim) = {
+			let _dest_dim = images[0].dimensions().width_height(); // _dest_dim is the inner size of the window
 			let _output_dim = output_img.dimensions().width_height();
-			let (w, h) = (_dest_dim[0] as f32, _dest_dim[1] as f32); // _dest_dim is the inner size of the window
 			
 			(
 				[_dest_dim[0] as i32, _dest_dim[1] as i32, 1],
-				[_output_dim[0] as i32, _output_dim[1] as i32, 1],
-				(w / _output_dim[0] as f32, h / _output_dim[1] as f32)
+				[_output_dim[0] as i32, _output_dim[1] as i32, 1]
 			)
 		};
 
@@ -195,16 +230,20 @@ impl<Ds: 'static, Update: 'static, Resize: 'static, Pc, DsBuilder: 'static> Canv
 		let mut frames = 0;
 		let dt_log_rate = 300;
 		let mut t = 0.0; // Global time for animation, passed to update closure
-		let (mut push_constants, mut need_update) = update(None, t);
+		update(None, t);
 		let mut recording = false;
 		let mut current_frame = 0;
 		let root_folder = current_dir().unwrap();
-		let mut recording_path = root_folder.clone();
+		let capture_dir = root_folder.join(&self.capture_dir);
+		let cursor_grab = self.cursor_grab;
+		let mut cursor_grabbed = false;
+		let recording_flag = self.recording; // Can also be set by the update closure
+		let shader_rebuild = self.shader_rebuild;
+		let mut pending_rebuild = None; // Kept until the shader compiles with it
+		let mut last_rebuild = Instant::now(); // A pending rebuild is retried every second, the texture count of the scene doesn't fit the shader until it compiles
 
 		event_loop.run(move |ev: Event<()>, _, control_flow| {
-			let update_res = update(Some(&ev), t);
-			push_constants = update_res.0;
-			need_update = update_res.1;
+			let (push_constants, need_update) = update(Some(&ev), t);
 
 			if need_update {
 				resized = true;
@@ -231,18 +270,7 @@ impl<Ds: 'static, Update: 'static, Resize: 'static, Pc, DsBuilder: 'static> Canv
 								match kb_input.scancode {
 									1 => *control_flow = ControlFlow::Exit, // Escape
 									60 => { // F2, start or stop recording
-										if !recording {
-											recording = true;
-											let mut timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false);
-											timestamp = timestamp.replace(":", "_");
-											println!("Using folder name: {}", timestamp);
-											recording_path = path::PathBuf::from(format!("Captures/{}", timestamp));
-											fs::create_dir_all(&recording_path).unwrap();
-											set_current_dir(&recording_path).unwrap();
-										} else {
-											recording = false;
-											set_current_dir(&root_folder).unwrap();
-										}
+										recording_flag.fetch_xor(true, Ordering::Relaxed);
 									}
 									_ => ()
 								}
@@ -263,6 +291,71 @@ impl<Ds: 'static, Update: 'static, Resize: 'static, Pc, DsBuilder: 'static> Canv
 						println!("Frame time: {}ms, FPS: {}", elapsed.as_secs_f32() * 1000.0 / dt_log_rate as f32, dt_log_rate as f32 / elapsed.as_secs_f32());
 					}
 
+					let grab = cursor_grab.load(Ordering::Relaxed);
+					if grab != cursor_grabbed {
+						cursor_grabbed = grab;
+						if let Err(e) = surface.window().set_cursor_grab(grab) {
+							println!("Failed to grab the cursor: {}", e);
+						}
+						surface.window().set_cursor_visible(!grab);
+					}
+
+					if recording_flag.load(Ordering::Relaxed) != recording {
+						if !recording {
+							recording = true;
+							let mut timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false);
+							timestamp = timestamp.replace(":", "_");
+							println!("Using folder name: {}", timestamp);
+							let recording_path = capture_dir.join(timestamp);
+							fs::create_dir_all(&recording_path).unwrap();
+							set_current_dir(&recording_path).unwrap();
+						} else {
+							recording = false;
+							set_current_dir(&root_folder).unwrap();
+						}
+					}
+
+					let requested = shader_rebuild.lock().unwrap().take();
+					let defines_changed = requested.is_some();
+					if requested.is_some() {
+						pending_rebuild = requested;
+					}
+					let changed = shader_watcher.poll();
+					let retry = pending_rebuild.is_some() && last_rebuild.elapsed() >= Duration::from_secs(1);
+					if defines_changed || retry || !changed.is_empty() { // Hot reload, the previous pipeline keeps rendering when the new source doesn't compile
+						last_rebuild = Instant::now();
+						if defines_changed {
+							println!("The shader defines changed, compiling the shader again");
+						} else if changed.is_empty() {
+							println!("Compiling the shader again with the pending defines");
+						} else {
+							println!("{} changed, reloading the shader", changed[0].display());
+						}
+						let compiled = match &pending_rebuild {
+							Some((layout, defines)) => shader.recompile(device.clone(), layout.clone(), defines),
+							None => shader.reload(device.clone())
+						};
+						let reloaded = compiled.and_then(|s| match ComputePipeline::new(device.clone(), &s.main_entry_point(), &(), None) {
+							Ok(pipeline) => Ok((s, pipeline)),
+							Err(e) => Err(loader::CompileError { message: format!("Failed to create the compute pipeline: {:?}", e), sources: s.sources().to_vec() })
+						});
+						match reloaded {
+							Ok((s, pipeline)) => {
+								shader_watcher.watch(s.sources().iter().cloned()); // The includes may have changed
+								shader = s;
+								pending_rebuild = None;
+								compute_pipeline = Arc::new(pipeline);
+								layout = compute_pipeline.layout().descriptor_set_layout(0).unwrap().to_owned();
+								resized = true; // Rebuilds the descriptor set for the new pipeline and restarts the update closure, so the accumulation restarts
+								println!("Shader reloaded");
+							},
+							Err(e) => {
+								eprintln!("{}", e);
+								shader_watcher.watch(e.sources); // Includes added by the failed attempt are watched before the shader compiles again
+							}
+						}
+					}
+
 					if minimized { return; } // Don't try anything if the window is minimized, this prevents the errors creating images with 0 sizes
 
 					
@@ -306,7 +399,6 @@ impl<Ds: 'static, Update: 'static, Resize: 'static, Pc, DsBuilder: 'static> Canv
 						images = new_images;
 
 						let _dest_dim = images[0].dimensions();
-						let _output_dim = [(_dest_dim.width() as f32 / scale.0).floor() as u32, (_dest_dim.height() as f32 / scale.1).floor() as u32];
 						
 						// let r = (ds_builder.clone())(surface.window().inner_size(), device.clone(), queue.clone(), layout.clone());
 						let inner_size = surface.window().inner_size();
@@ -332,8 +424,9 @@ impl<Ds: 'static, Update: 'static, Resize: 'static, Pc, DsBuilder: 'static> Canv
 						dispatch = r.2;
 						update = r.3;
 
+						let _output_dim = output_img.dimensions(); // The resize closure decides of the rendering resolution
 						dest_dim = [_dest_dim.width() as i32, _dest_dim.height() as i32, 1];
-						output_dim = [_output_dim[0] as i32, _output_dim[1] as i32, 1];
+						output_dim = [_output_dim.width() as i32, _output_dim.height() as i32, 1];
 					}
 
 					let (swap_index, suboptimal, acquire_future) = match swapchain::acquire_next_image(swapchain.clone(), None) {
@@ -349,7 +442,7 @@ impl<Ds: 'static, Update: 'static, Resize: 'static, Pc, DsBuilder: 'static> Canv
 						resized = true;
 					}
 					
-					dest_image = images[swap_index].clone();
+					let dest_image = images[swap_index].clone(); // This arc references the image being rendered to
 
 					let mut cb_builder = AutoCommandBufferBuilder::primary(device.clone(), queue.family()).unwrap();
 					
@@ -0,0 +1,208 @@
+//! Descriptor set whose content is only known at runtime
+//! `PersistentDescriptorSet` stores every element in its type, so its arrays can't be sized from data loaded at runtime
+
+use vulkano::buffer::{BufferAccess, BufferInner};
+use vulkano::descriptor::descriptor::{DescriptorDesc, DescriptorType};
+use vulkano::descriptor::descriptor_set::{DescriptorSet, DescriptorSetDesc, DescriptorPool, DescriptorPoolAlloc, DescriptorWrite};
+use vulkano::descriptor::descriptor_set::{StdDescriptorPoolAlloc, UnsafeDescriptorSet, UnsafeDescriptorSetLayout};
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::image::view::ImageViewAbstract;
+use vulkano::sampler::Sampler;
+use vulkano::VulkanObject;
+
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+type Buffer = Box<dyn BufferAccess + Send + Sync>;
+type Image = Box<dyn ImageViewAbstract + Send + Sync>;
+
+pub struct DynamicDescriptorSet {
+	inner: StdDescriptorPoolAlloc,
+	layout: Arc<UnsafeDescriptorSetLayout>,
+
+	// Kept alive as long as the set, with the binding using them
+	buffers: Vec<(Buffer, u32)>,
+	images: Vec<(Image, u32)>,
+	_samplers: Vec<Arc<Sampler>>,
+}
+
+impl DynamicDescriptorSet {
+	/// Bindings are added in order like with `PersistentDescriptorSet::start`
+	pub fn start(layout: Arc<UnsafeDescriptorSetLayout>) -> DynamicDescriptorSetBuilder {
+		DynamicDescriptorSetBuilder {
+			layout,
+			binding: 0,
+			writes: Vec::new(),
+			buffers: Vec::new(),
+			images: Vec::new(),
+			samplers: Vec::new(),
+			error: None
+		}
+	}
+}
+
+pub struct DynamicDescriptorSetBuilder {
+	layout: Arc<UnsafeDescriptorSetLayout>,
+	binding: u32, // Next binding to fill
+	writes: Vec<DescriptorWrite>,
+	buffers: Vec<(Buffer, u32)>,
+	images: Vec<(Image, u32)>,
+	samplers: Vec<Arc<Sampler>>,
+	error: Option<String>, // First element that can't be bound, returned by `build`
+}
+
+impl DynamicDescriptorSetBuilder {
+	/// Binds a storage buffer, a buffer without the storage usage, badly aligned or at a binding of another type makes `build` fail
+	pub fn add_buffer<B>(mut self, buffer: B) -> Self
+	where B: BufferAccess + Send + Sync + 'static {
+		match self.check_binding(DescriptorType::StorageBuffer, 1).and_then(|_| check_storage_buffer(&buffer)) {
+			// Safety: the type of the binding and the usage, offset alignment and range required by vkUpdateDescriptorSets were checked
+			Ok(()) => self.writes.push(unsafe { DescriptorWrite::storage_buffer(self.binding, 0, &buffer) }),
+			Err(e) => self.fail(e)
+		}
+		self.buffers.push((Box::new(buffer), self.binding));
+		self.binding += 1;
+		self
+	}
+
+	/// Binds a storage image, an image at a binding of another type makes `build` fail
+	pub fn add_image<I>(mut self, image: I) -> Self
+	where I: ImageViewAbstract + Send + Sync + 'static {
+		match self.check_binding(DescriptorType::StorageImage, 1) {
+			Ok(()) => self.writes.push(DescriptorWrite::storage_image(self.binding, 0, &image)),
+			Err(e) => self.fail(e)
+		}
+		self.images.push((Box::new(image), self.binding));
+		self.binding += 1;
+		self
+	}
+
+	/// Fills an array of combined image samplers, the number of images must match the array size declared by the layout
+	pub fn add_sampled_image_array<I>(mut self, images: Vec<(I, Arc<Sampler>)>) -> Result<Self, String>
+	where I: ImageViewAbstract + Send + Sync + 'static {
+		self.check_binding(DescriptorType::CombinedImageSampler, images.len() as u32)
+			.map_err(|e| format!("Binding {}: {}", self.binding, e))?;
+
+		for (i, (image, sampler)) in images.into_iter().enumerate() {
+			self.writes.push(DescriptorWrite::combined_image_sampler(self.binding, i as u32, &sampler, &image));
+			self.images.push((Box::new(image), self.binding));
+			self.samplers.push(sampler);
+		}
+		self.binding += 1;
+		Ok(self)
+	}
+
+	// The layout must declare `count` descriptors of this type at the next binding, the writes of `build` aren't checked by vulkano
+	fn check_binding(&self, ty: DescriptorType, count: u32) -> Result<(), String> {
+		match self.layout.descriptor(self.binding as usize) {
+			None => Err(format!("the layout has no descriptor at this binding, expected {:?}", ty)),
+			Some(d) if d.ty.ty() != ty => Err(format!("the layout declares a {:?}, not a {:?}", d.ty.ty(), ty)),
+			Some(d) if d.array_count != count => Err(format!("the layout declares {} descriptors, got {}", d.array_count, count)),
+			Some(_) => Ok(())
+		}
+	}
+
+	// Keeps the first error for `build`
+	fn fail(&mut self, e: String) {
+		if self.error.is_none() {
+			self.error = Some(format!("Binding {}: {}", self.binding, e));
+		}
+	}
+
+	pub fn build(self) -> Result<DynamicDescriptorSet, String> {
+		if let Some(e) = self.error {
+			return Err(e);
+		}
+		let expected = self.layout.num_bindings();
+		if self.binding as usize != expected {
+			return Err(format!("The layout has {} bindings, {} were filled", expected, self.binding));
+		}
+
+		let mut pool = Device::standard_descriptor_pool(self.layout.device());
+		let mut inner = pool.alloc(&self.layout)
+			.map_err(|e| format!("Failed to allocate the descriptor set: {}", e))?;
+		unsafe {
+			inner.inner_mut().write(self.layout.device(), self.writes.into_iter());
+		}
+
+		Ok(DynamicDescriptorSet {
+			inner,
+			layout: self.layout,
+			buffers: self.buffers,
+			images: self.images,
+			_samplers: self.samplers
+		})
+	}
+}
+
+// Requirements of a storage buffer descriptor that vulkano only checks in debug builds, or not at all
+fn check_storage_buffer<B: BufferAccess>(buffer: &B) -> Result<(), String> {
+	let size = buffer.size();
+	let BufferInner { buffer, offset } = buffer.inner();
+	let limits = buffer.device().physical_device().limits();
+
+	if !buffer.usage_storage_buffer() {
+		return Err(String::from("the buffer was not created with the storage buffer usage"));
+	}
+	let alignment = limits.min_storage_buffer_offset_alignment() as usize;
+	if offset % alignment != 0 {
+		return Err(format!("the buffer offset {} is not a multiple of {}", offset, alignment));
+	}
+	if size > limits.max_storage_buffer_range() as usize {
+		return Err(format!("the buffer is {} bytes, storage buffers are limited to {}", size, limits.max_storage_buffer_range()));
+	}
+	Ok(())
+}
+
+unsafe impl DescriptorSet for DynamicDescriptorSet {
+	fn inner(&self) -> &UnsafeDescriptorSet {
+		self.inner.inner()
+	}
+
+	fn num_buffers(&self) -> usize {
+		self.buffers.len()
+	}
+
+	fn buffer(&self, index: usize) -> Option<(&dyn BufferAccess, u32)> {
+		self.buffers.get(index).map(|(b, binding)| (&**b as &dyn BufferAccess, *binding))
+	}
+
+	fn num_images(&self) -> usize {
+		self.images.len()
+	}
+
+	fn image(&self, index: usize) -> Option<(&dyn ImageViewAbstract, u32)> {
+		self.images.get(index).map(|(i, binding)| (&**i as &dyn ImageViewAbstract, *binding))
+	}
+}
+
+unsafe impl DescriptorSetDesc for DynamicDescriptorSet {
+	fn num_bindings(&self) -> usize {
+		self.layout.num_bindings()
+	}
+
+	fn descriptor(&self, binding: usize) -> Option<DescriptorDesc> {
+		self.layout.descriptor(binding)
+	}
+}
+
+unsafe impl DeviceOwned for DynamicDescriptorSet {
+	fn device(&self) -> &Arc<Device> {
+		self.layout.device()
+	}
+}
+
+impl PartialEq for DynamicDescriptorSet {
+	fn eq(&self, other: &Self) -> bool {
+		self.inner().internal_object() == other.inner().internal_object() && self.device() == other.device()
+	}
+}
+
+impl Eq for DynamicDescriptorSet {}
+
+impl Hash for DynamicDescriptorSet {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.inner().internal_object().hash(state);
+		self.device().hash(state);
+	}
+}
@@ -0,0 +1,173 @@
+use crate::{util, loader};
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBuffer};
+use vulkano::device::{Device, DeviceOwned, Queue};
+use vulkano::image::{StorageImage, ImageUsage, ImageAccess, ImageCreateFlags, ImageDimensions};
+use vulkano::instance::{Instance, ApplicationInfo};
+use vulkano::format::{Format, ClearValue};
+use vulkano::sampler::Filter;
+use vulkano::sync::GpuFuture;
+use vulkano::pipeline::{ComputePipeline, shader::SpecializationConstants};
+use vulkano::descriptor::{descriptor_set::UnsafeDescriptorSetLayout, PipelineLayoutAbstract, DescriptorSet, descriptor_set::DescriptorSetDesc};
+
+use winit::event::Event;
+use winit::dpi::PhysicalSize;
+
+use image;
+
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::Instant;
+use std::fmt::Debug;
+use std::fs;
+use std::path::Path;
+
+// Renders the output of the compute shaders to png files without any window, surface or swapchain
+// Uses the same closures as `Canvas` so a program can pick between the two at runtime
+pub struct Headless<Ds, Update, Resize, Pc: 'static, DsBuilder> where
+	Ds: DescriptorSet + DescriptorSetDesc + DeviceOwned + Eq + Hash + PartialEq + Send + Sync,
+	Update: FnMut(Option<&winit::event::Event<()>>, f64) -> (Pc, bool),
+	Resize: FnMut(PhysicalSize<u32>, Arc<Device>, Arc<Queue>, Arc<UnsafeDescriptorSetLayout>) -> (Arc<Ds>, Arc<StorageImage<Format>>, [u32; 3], Update) + Clone,
+	Pc: SpecializationConstants + Copy + Debug,
+	DsBuilder: FnOnce(PhysicalSize<u32>, Arc<Device>, Arc<Queue>, Arc<UnsafeDescriptorSetLayout>) -> (Arc<Ds>, Arc<StorageImage<Format>>, [u32; 3], Update, Resize) + Clone {
+	ds_builder: DsBuilder,
+
+	// Vulkan specific attributes
+	_instance: Arc<Instance>,
+	pub device: Arc<Device>,
+	pub queue: Arc<Queue>,
+	pub size: PhysicalSize<u32>, // Plays the role of the window size, passed to ds_builder
+	pub shader: Option<loader::Shader>,
+}
+
+impl<Ds: 'static, Update: 'static, Resize: 'static, Pc, DsBuilder: 'static> Headless<Ds, Update, Resize, Pc, DsBuilder> where
+	Ds: DescriptorSet + DescriptorSetDesc + DeviceOwned + Eq + Hash + PartialEq + Send + Sync,
+	Update: FnMut(Option<&winit::event::Event<()>>, f64) -> (Pc, bool),
+	Resize: FnMut(PhysicalSize<u32>, Arc<Device>, Arc<Queue>, Arc<UnsafeDescriptorSetLayout>) -> (Arc<Ds>, Arc<StorageImage<Format>>, [u32; 3], Update) + Clone,
+	Pc: SpecializationConstants + Copy + Debug,
+	DsBuilder: FnOnce(PhysicalSize<u32>, Arc<Device>, Arc<Queue>, Arc<UnsafeDescriptorSetLayout>) -> (Arc<Ds>, Arc<StorageImage<Format>>, [u32; 3], Update, Resize) + Clone {
+
+	/// See `Canvas::new` for the description of the `ds_builder` closure
+	/// The `Update` closure receives `Event::RedrawEventsCleared` before every frame, no other event is emitted
+	pub fn new(size: PhysicalSize<u32>, ds_builder: DsBuilder, app_info: &ApplicationInfo, device_index: Option<usize>) -> Self {
+		let (instance, device, queue) = util::init_vulkano_headless(app_info, device_index);
+
+		Self {
+			ds_builder,
+			_instance: instance,
+			device,
+			queue,
+			size,
+			shader: None,
+		}
+	}
+
+	pub fn set_shader(&mut self, cs: loader::Shader) {
+		self.shader = Some(cs);
+	}
+
+	/// #### Arguments
+	/// - `frames` number of frames to render
+	/// - `t_step` increment of the `t` passed to the `update` closure after each frame, `t` starts at 0
+	/// - `output_dir` folder in which the frames are saved as `<frame>.png`
+	pub fn run<P: AsRef<Path>>(self, frames: u32, t_step: f64, output_dir: P) -> Result<(), String> {
+		let shader = match self.shader {
+			Some(s) => s,
+			None => return Err(String::from("The shader was not set"))
+		};
+
+		let device = self.device;
+		let queue = self.queue;
+		let size = self.size;
+		let output_dir = output_dir.as_ref();
+
+		if let Err(e) = fs::create_dir_all(output_dir) {
+			return Err(format!("Failed to create {}: {:?}", output_dir.display(), e));
+		}
+
+		let compute_pipeline = Arc::new(ComputePipeline::new(
+			device.clone(),
+			&shader.main_entry_point(),
+			&(),
+			None
+		).unwrap());
+
+		let layout = compute_pipeline.layout().descriptor_set_layout(0).unwrap().to_owned();
+
+		let (mut descriptor_set, mut output_img, mut dispatch, mut update, resize) = (self.ds_builder)(size, device.clone(), queue.clone(), layout.clone());
+
+		let mut t = 0.0; // Global time for animation, passed to update closure
+		update(None, t);
+
+		let start = Instant::now();
+		for frame in 0..frames {
+			let (push_constants, need_update) = update(Some(&Event::RedrawEventsCleared), t);
+
+			if need_update {
+				let r = (resize.clone())(size, device.clone(), queue.clone(), layout.clone());
+				descriptor_set = r.0;
+				output_img = r.1;
+				dispatch = r.2;
+				update = r.3;
+			}
+
+			// The output image is blitted to a RGBA image to get the channels in the order expected by the png encoder
+			let output_dim = output_img.dimensions();
+			let save_image = StorageImage::with_usage(
+				device.clone(), ImageDimensions::Dim2d { width: output_dim.width(), height: output_dim.height(), array_layers: 1 }, Format::R8G8B8A8Unorm,
+				ImageUsage {
+					transfer_source: true,
+					transfer_destination: true,
+					.. ImageUsage::none()
+				},
+				ImageCreateFlags::none(),
+				vec![queue.family()]
+			).unwrap();
+
+			let image_save_buffer = CpuAccessibleBuffer::from_iter(device.clone(), BufferUsage::all(), false,
+				(0 .. output_dim.width() * output_dim.height() * 4).map(|_| 0u8))
+				.expect("failed to create image_save_buffer");
+
+			let blit_dim = [output_dim.width() as i32, output_dim.height() as i32, 1];
+
+			let mut cb_builder = AutoCommandBufferBuilder::primary(device.clone(), queue.family()).unwrap();
+			cb_builder
+				.clear_color_image(output_img.clone(), ClearValue::Float([0.0, 0.0, 0.0, 1.0])).unwrap()
+				.dispatch(dispatch, compute_pipeline.clone(), descriptor_set.clone(), push_constants, std::iter::empty()).unwrap()
+				.blit_image(
+					output_img.clone(),
+					[0, 0, 0],
+					blit_dim,
+					0,
+					0,
+					save_image.clone(),
+					[0, 0, 0],
+					blit_dim,
+					0,
+					0,
+					1,
+					Filter::Nearest
+				).unwrap()
+				.copy_image_to_buffer(save_image.clone(), image_save_buffer.clone()).unwrap();
+
+			let cb = cb_builder.build().unwrap();
+			cb.execute(queue.clone()).unwrap()
+				.then_signal_fence_and_flush().unwrap()
+				.wait(None).unwrap();
+
+			let data = image_save_buffer.read().unwrap();
+			let to_save = image::ImageBuffer::<image::Rgba<u8>, _>::from_raw(output_dim.width(), output_dim.height(), &data[..]).unwrap();
+			let frame_path = output_dir.join(format!("{}.png", frame));
+			if let Err(e) = to_save.save(&frame_path) {
+				return Err(format!("Failed to save {}: {:?}", frame_path.display(), e));
+			}
+
+			t += t_step;
+		}
+
+		let elapsed = start.elapsed();
+		println!("Rendered {} frames in {}s, frame time: {}ms", frames, elapsed.as_secs_f32(), elapsed.as_secs_f32() * 1000.0 / frames.max(1) as f32);
+
+		Ok(())
+	}
+}
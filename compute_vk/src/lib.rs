@@ -7,5 +7,14 @@ pub extern crate image;
 pub mod loader;
 pub mod util;
 
+// Descriptor sets with arrays sized at runtime
+pub mod descriptor;
+
 // Module for creating a winit window linked the the Vulkan context
-pub mod canvas;
\ No newline at end of file
+pub mod canvas;
+
+// Module for rendering to image files without any window
+pub mod headless;
+
+// Polls files for changes, used to reload the shader while the window is open
+pub mod watch;
\ No newline at end of file
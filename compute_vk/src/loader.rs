@@ -12,13 +12,31 @@ use vulkano::descriptor::pipeline_layout::PipelineLayoutDesc;
 use vulkano::descriptor::pipeline_layout::PipelineLayoutDescPcRange;
 
 use shaderc;
-use relative_path::{RelativePath, RelativePathBuf};
 
+use std::cell::RefCell;
+use std::fmt;
 use std::fs::read;
+use std::path::{Path, PathBuf};
+
+/// Error of a compilation, with the files read before it failed so they can be watched until the shader compiles again
+#[derive(Debug)]
+pub struct CompileError {
+    pub message: String,
+    pub sources: Vec<PathBuf>, // Includes the file that failed to be included
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
 
 pub struct Shader {
     shader : std::sync::Arc<vulkano::pipeline::shader::ShaderModule>,
-    layout: MainLayout
+    layout: MainLayout,
+    path: PathBuf, // Absolute, so the shader can be compiled again after the working directory changed
+    defines: Vec<(String, String)>,
+    sources: Vec<PathBuf> // The shader file and every file it included
 } 
 
 impl Shader {
@@ -26,77 +44,118 @@ impl Shader {
     #[inline]
     #[allow(unsafe_code)] pub fn
     load(device: std::sync::Arc<vulkano::device::Device>, filename: &str, layout: MainLayout) -> Result<Shader, String> {
+        Self::load_with_defines(device, filename, layout, &[])
+    }
+
+    /// Same as `load`, each `(name, value)` pair is defined as a macro before compiling
+    pub fn load_with_defines(device: std::sync::Arc<vulkano::device::Device>, filename: &str, layout: MainLayout, defines: &[(&str, String)]) -> Result<Shader, String> {
         if !device.enabled_features().shader_storage_image_extended_formats {
             panic !
             ("Device feature {:?} required",
              "shader_storage_image_extended_formats");
         }
-		
-        let src = match read(filename) {
-            Err(e) => return Err(String::from(format!("Failed to load {}: {:?}", filename, e))),
+
+        let path = std::fs::canonicalize(filename).map_err(|e| format!("Failed to load {}: {:?}", filename, e))?;
+        let defines = defines.iter().map(|(name, value)| (name.to_string(), value.clone())).collect::<Vec<_>>();
+        Self::compile(device, path, layout, defines).map_err(|e| e.message)
+    }
+
+    /// Compiles the same files again with the same defines, used to reload the shader after its sources changed
+    pub fn reload(&self, device: std::sync::Arc<vulkano::device::Device>) -> Result<Shader, CompileError> {
+        Self::compile(device, self.path.clone(), self.layout.clone(), self.defines.clone())
+    }
+
+    /// Compiles the same files with other defines and layout, used when a define changes the size of a binding
+    pub fn recompile(&self, device: std::sync::Arc<vulkano::device::Device>, layout: MainLayout, defines: &[(&str, String)]) -> Result<Shader, CompileError> {
+        let defines = defines.iter().map(|(name, value)| (name.to_string(), value.clone())).collect::<Vec<_>>();
+        Self::compile(device, self.path.clone(), layout, defines)
+    }
+
+    /// The shader file and the files it included, in include order
+    pub fn sources(&self) -> &[PathBuf] {
+        &self.sources
+    }
+
+    fn compile(device: std::sync::Arc<vulkano::device::Device>, path: PathBuf, layout: MainLayout, defines: Vec<(String, String)>) -> Result<Shader, CompileError> {
+        let filename = &*path.to_string_lossy();
+        let error = |message: String| CompileError { message, sources: vec![path.clone()] };
+        let src = match read(&path) {
+            Err(e) => return Err(error(format!("Failed to load {}: {:?}", filename, e))),
             Ok(data) => {
                 match String::from_utf8(data) {
                     Ok(s) => s,
-                    Err(e) => return Err(String::from(format!("Failed to parse source from {}: {:?}", filename, e)))
+                    Err(e) => return Err(error(format!("Failed to parse source from {}: {:?}", filename, e)))
                 }
             }
         };
 
         let mut c = shaderc::Compiler::new().unwrap();
 
+        let sources = RefCell::new(vec![path.clone()]);
         let mut compile_options = shaderc::CompileOptions::new().unwrap();
+        for (name, value) in defines.iter() {
+            compile_options.add_macro_definition(name, Some(value));
+        }
         compile_options.set_include_callback(|requested_name, include_type, requesting_name, _depth| {
 
-            let requesting_path = RelativePath::new(requesting_name);
+            let requesting_path = Path::new(requesting_name);
 
             let filename = match include_type {
                 shaderc::IncludeType::Relative => {
                     match requesting_path.parent() {
-                        Some(parent_dir) => {
-                            let relative_to_parent = parent_dir.join_normalized(requested_name);
-                            relative_to_parent
-                        },
-                        None => return Err(String::from(format!("No parent for {}", requesting_path)))
+                        Some(parent_dir) => parent_dir.join(requested_name),
+                        None => return Err(String::from(format!("No parent for {}", requesting_path.display())))
                     }
                 },
                 shaderc::IncludeType::Standard => {
-                    RelativePathBuf::from_path(requested_name).unwrap()
+                    PathBuf::from(requested_name)
                 },
             };
 
-            let content = match read(filename.as_str()) {
-                Err(e) => return Err(String::from(format!("Cannot include {} to {} using path {}: {:?}", requested_name, requesting_name, filename, e))),
+            if !sources.borrow().contains(&filename) { // Before reading it, a file that doesn't exist yet is watched too
+                sources.borrow_mut().push(filename.clone());
+            }
+
+            let content = match read(&filename) {
+                Err(e) => return Err(String::from(format!("Cannot include {} to {} using path {}: {:?}", requested_name, requesting_name, filename.display(), e))),
                 Ok(data) => {
                     match String::from_utf8(data) {
                         Ok(s) => s,
-                        Err(e) => return Err(String::from(format!("Cannot parse include from {} to {} using path {}: {:?}", requested_name, requesting_name, filename, e)))
+                        Err(e) => return Err(String::from(format!("Cannot parse include from {} to {} using path {}: {:?}", requested_name, requesting_name, filename.display(), e)))
                     }
                 }
             };
-            
+
+            // The full path is the requesting name of the includes of this file, so they're found relative to it
             Ok(
                 shaderc::ResolvedInclude {
-                    resolved_name: String::from(requested_name),
+                    resolved_name: filename.to_string_lossy().into_owned(),
                     content
                 }
             )
         });
 
-        let artifact = match c.compile_into_spirv(
+        let artifact = c.compile_into_spirv(
             src.as_str(),
             shaderc::ShaderKind::Compute,
             filename,
             "main",
             Some(&compile_options)
-        ) {
+        );
+        drop(compile_options); // Releases the include callback borrowing the sources
+        let sources = sources.into_inner();
+        let artifact = match artifact {
             Ok(a) => a,
-            Err(e) => return Err(String::from(format!("Failed to compile {}: {:?}", filename, e)))
+            Err(e) => return Err(CompileError { message: format!("Failed to compile {}: {}", filename, e), sources })
         };
 		 
 		unsafe {
             Ok(Shader {
 				shader: vulkano::pipeline::shader::ShaderModule::new(device, artifact.as_binary_u8()).unwrap(),
-                layout
+                layout,
+                path,
+                defines,
+                sources
             })
         }
 
@@ -112,7 +171,7 @@ impl Shader {
     #[doc = r" Returns a logical struct describing the entry point named `{ep_name}`."]
     #[inline]
 	#[allow(unsafe_code)]
-	pub fn main_entry_point(&self) -> vulkano::pipeline::shader::ComputeEntryPoint<(), MainLayout> {
+	pub fn main_entry_point(&self) -> vulkano::pipeline::shader::ComputeEntryPoint<'_, (), MainLayout> {
         unsafe {
             #[allow(dead_code)]
 			static NAME : [u8 ; 5usize] = [109u8, 97u8, 105u8, 110u8, 0]; // Entry point function must be "main"
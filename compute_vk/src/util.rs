@@ -1,5 +1,5 @@
 use vulkano::device::{Device, Queue, Features, DeviceExtensions};
-use vulkano::instance::{Instance, PhysicalDevice, ApplicationInfo};
+use vulkano::instance::{Instance, InstanceExtensions, PhysicalDevice, ApplicationInfo};
 use vulkano::image::{ImageDimensions, StorageImage, ImageUsage, ImageCreateFlags};
 use vulkano::buffer::{CpuAccessibleBuffer, DeviceLocalBuffer, BufferUsage, BufferAccess};
 use vulkano::format::{Format, ClearValue};
@@ -10,16 +10,49 @@ use vulkano::memory::Content;
 use std::sync::Arc;
 use std::any::type_name;
 
-pub fn init_vulkano(app_info: &ApplicationInfo) -> (Arc<Instance>, Arc<Device>, Arc<Queue>) {
+/// `device_index` selects the physical device to use, the first one is used when `None`
+pub fn init_vulkano(app_info: &ApplicationInfo, device_index: Option<usize>) -> (Arc<Instance>, Arc<Device>, Arc<Queue>) {
 	let instance = Instance::new(
 		Some(app_info),
 		&vulkano_win::required_extensions(),
 		None
 	).expect("Failed to create Vulkan instance");
 
+	let _ext = DeviceExtensions {
+		khr_storage_buffer_storage_class: true, // Needed for creating buffers on GPU memory
+		khr_swapchain: true,
+		.. DeviceExtensions::none()
+	};
+
+	let (device, queue) = create_device(&instance, device_index, &_ext);
+	(instance, device, queue)
+}
+
+/// Same as `init_vulkano` but doesn't enable any surface or swapchain extension, used for offscreen rendering
+pub fn init_vulkano_headless(app_info: &ApplicationInfo, device_index: Option<usize>) -> (Arc<Instance>, Arc<Device>, Arc<Queue>) {
+	let instance = Instance::new(
+		Some(app_info),
+		&InstanceExtensions::none(),
+		None
+	).expect("Failed to create Vulkan instance");
+
+	let _ext = DeviceExtensions {
+		khr_storage_buffer_storage_class: true, // Needed for creating buffers on GPU memory
+		.. DeviceExtensions::none()
+	};
+
+	let (device, queue) = create_device(&instance, device_index, &_ext);
+	(instance, device, queue)
+}
+
+fn create_device(instance: &Arc<Instance>, device_index: Option<usize>, _ext: &DeviceExtensions) -> (Arc<Device>, Arc<Queue>) {
 	// Getting the physical devices
-	let mut _device = PhysicalDevice::enumerate(&instance)
-		.next().expect("No devices available");
+	let _device = match device_index {
+		Some(i) => PhysicalDevice::from_index(instance, i)
+			.unwrap_or_else(|| panic!("No device with index {}", i)),
+		None => PhysicalDevice::enumerate(instance)
+			.next().expect("No devices available")
+	};
 
 	println!("Using device {}, type: {:?}", _device.name(), _device.ty());
 
@@ -33,19 +66,26 @@ pub fn init_vulkano(app_info: &ApplicationInfo) -> (Arc<Instance>, Arc<Device>,
 		.. Features::none()
 	};
 
-	let _ext = DeviceExtensions {
-		khr_storage_buffer_storage_class: true, // Needed for creating buffers on GPU memory
-		khr_swapchain: true,
-		.. DeviceExtensions::none()
-	};
-
-	let (device, mut _queues) = Device::new(_device, &_features, &_ext, [(queue_family, 0.5)].iter().cloned())
+	let (device, mut _queues) = Device::new(_device, &_features, _ext, [(queue_family, 0.5)].iter().cloned())
 		.expect("Failed to create a device");
 
 	// Getting a queue
 	let queue = _queues.next().unwrap();
 
-	(instance, device, queue)
+	(device, queue)
+}
+
+/// Returns the index, name and type of every physical device
+pub fn list_devices(app_info: &ApplicationInfo) -> Vec<(usize, String, String)> {
+	let instance = Instance::new(
+		Some(app_info),
+		&InstanceExtensions::none(), // No surface needed to enumerate the devices
+		None
+	).expect("Failed to create Vulkan instance");
+
+	PhysicalDevice::enumerate(&instance)
+		.map(|d| (d.index(), d.name().to_owned(), format!("{:?}", d.ty())))
+		.collect()
 }
 
 /// Creates the output image and returns the arc
@@ -0,0 +1,56 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Polls the modification times of a list of files, cheap enough to be called every frame
+pub struct FileWatcher {
+	files: Vec<(PathBuf, Option<SystemTime>)>, // None while the file can't be read, like in the middle of a save
+	interval: Duration, // The files are checked at most once per interval
+	last_poll: Instant,
+}
+
+impl FileWatcher {
+	pub fn new<I: IntoIterator<Item = PathBuf>>(files: I) -> Self {
+		let mut watcher = Self {
+			files: Vec::new(),
+			interval: Duration::from_millis(250),
+			last_poll: Instant::now()
+		};
+		watcher.watch(files);
+		watcher
+	}
+
+	/// Replaces the watched files, their current state is the reference for the next changes
+	pub fn watch<I: IntoIterator<Item = PathBuf>>(&mut self, files: I) {
+		self.files = files.into_iter().map(|f| {
+			let modified = modified(&f);
+			(f, modified)
+		}).collect();
+	}
+
+	pub fn files(&self) -> impl Iterator<Item = &Path> {
+		self.files.iter().map(|(f, _)| f.as_path())
+	}
+
+	/// Files modified, created or deleted since the previous poll, empty until the interval elapsed
+	pub fn poll(&mut self) -> Vec<PathBuf> {
+		if self.last_poll.elapsed() < self.interval {
+			return Vec::new();
+		}
+		self.last_poll = Instant::now();
+
+		let mut changed = Vec::new();
+		for (f, last) in self.files.iter_mut() {
+			let modified = modified(f);
+			if modified != *last {
+				*last = modified;
+				changed.push(f.clone());
+			}
+		}
+		changed
+	}
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+	fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,50 @@
+// Positions and weights of the rays inside the pixels, AA_SAMPLES, AA_PATTERN and AA_FILTER are set by antialiasing.rs
+
+#define PATTERN_STRATIFIED 0
+#define PATTERN_BLUE_NOISE 1
+
+#define FILTER_BOX 0
+#define FILTER_TENT 1
+#define FILTER_GAUSSIAN 2
+
+// Largest distance in pixels between a ray and the center of its pixel
+#if AA_FILTER == FILTER_BOX
+const float FILTER_RADIUS = 0.5;
+#elif AA_FILTER == FILTER_TENT
+const float FILTER_RADIUS = 1.0;
+#else
+const float FILTER_RADIUS = 1.5;
+#endif
+
+// Interleaved gradient noise, neighbouring pixels get very different values
+float ign(vec2 p) {
+	return fract(52.982918 * fract(dot(p, vec2(0.06711056, 0.00583715))));
+}
+
+// Position in [0, 1) of the k-th ray of the frame in the pixel, sample_id counts the rays since the accumulation started
+vec2 sample_position(ivec2 pixel, uint sample_id, uint k, inout uint seed) {
+#if AA_PATTERN == PATTERN_STRATIFIED
+	uint m = 1; // Cells per side of the grid
+	while (m * m < AA_SAMPLES) {
+		m++;
+	}
+	uint cell = k * m * m / AA_SAMPLES; // Spreads the rays over the grid when AA_SAMPLES isn't a square
+	float x = float(cell % m) + random(seed);
+	float y = float(cell / m) + random(seed);
+	return vec2(x, y) / float(m);
+#else
+	vec2 shift = vec2(ign(vec2(pixel)), ign(vec2(pixel) + vec2(47.0, 17.0)));
+	return fract(shift + float(sample_id) * vec2(0.7548777, 0.5698403)); // R2 sequence
+#endif
+}
+
+float filter_weight(vec2 offset) {
+#if AA_FILTER == FILTER_BOX
+	return 1.0;
+#elif AA_FILTER == FILTER_TENT
+	vec2 w = max(vec2(0.0), 1.0 - abs(offset));
+	return w.x * w.y;
+#else
+	return exp(-dot(offset, offset) / (2.0 * 0.5 * 0.5)); // Standard deviation of half a pixel
+#endif
+}
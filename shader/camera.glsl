@@ -0,0 +1,41 @@
+// Primary rays, generated from the push constants so a resize or a new projection uploads nothing
+
+const uint PROJECTION_PERSPECTIVE = 0;
+const uint PROJECTION_ORTHOGRAPHIC = 1;
+const uint PROJECTION_FISHEYE = 2;
+const uint PROJECTION_EQUIRECTANGULAR = 3;
+
+// Distance from the camera to the image plane in pixels, the field of view spans the width of the image
+float pixel_depth(ivec2 size) {
+	float half_fov = pc.view.fov / 2.0;
+	return (cos(half_fov) * float(size.x)) / (2.0 * sin(half_fov));
+}
+
+// Camera space ray through a point of the image, offset is in pixels from the top left corner of the pixel
+// Returns false outside of the circle of a fisheye wider than 360 degrees
+bool primary_ray(ivec2 pixel, ivec2 size, vec2 offset, out Ray r) {
+	float x = float(pixel.x) - float(size.x) / 2.0 + offset.x; // From the center of the image, y up
+	float y = -(float(pixel.y) - float(size.y) / 2.0) - offset.y;
+	r.origin = vec4(0.0);
+
+	if (pc.view.projection == PROJECTION_PERSPECTIVE) {
+		r.dir = vec4(normalize(vec3(x, y, pixel_depth(size))), 0.0);
+	} else if (pc.view.projection == PROJECTION_ORTHOGRAPHIC) {
+		float scale = pc.view.ortho_width / float(size.x);
+		r.origin = vec4(x * scale, y * scale, 0.0, 0.0);
+		r.dir = vec4(0.0, 0.0, 1.0, 0.0);
+	} else if (pc.view.projection == PROJECTION_FISHEYE) {
+		float d = sqrt(x * x + y * y);
+		float theta = d / (float(size.x) / 2.0) * (pc.view.fov / 2.0); // Angle from the center of the view
+		if (theta > PI) {
+			return false;
+		}
+		vec2 side = d > 0.0 ? vec2(x, y) / d * sin(theta) : vec2(0.0);
+		r.dir = vec4(side, cos(theta), 0.0);
+	} else {
+		float longitude = (x / float(size.x)) * 2.0 * PI;
+		float latitude = (y / float(size.y)) * PI;
+		r.dir = vec4(cos(latitude) * sin(longitude), sin(latitude), cos(latitude) * cos(longitude), 0.0);
+	}
+	return true;
+}
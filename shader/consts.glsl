@@ -1,5 +1,19 @@
 const float RAY_COLLISION_PRECISION = 0.001;
-const uint REFLECT_DEPTH = 2;
+const uint REFLECT_DEPTH = 4; // Seeing through a transparent object takes three surfaces
+const uint RAY_STACK_SIZE = REFLECT_DEPTH + 1; // Each depth leaves at most one ray waiting on the stack
+const uint PATH_DEPTH = 8; // Most surfaces hit by a path of the path tracer
+const uint ROULETTE_BOUNCE = 2; // Paths can be stopped by Russian roulette from this bounce on
 const uint FACE_COUNT = 100;
+const uint BVH_STACK_SIZE = 32; // Deepest BVH built on the CPU plus one
 const float PI = 3.1415926538;
-const float HALF_PI = PI / 2.0;
\ No newline at end of file
+const float HALF_PI = PI / 2.0;
+
+#ifndef TEXTURE_COUNT
+#define TEXTURE_COUNT 1 // Set from the number of textures of the scene when the shader is compiled
+#endif
+
+#ifndef AA_SAMPLES
+#define AA_SAMPLES 1 // Anti-aliasing settings, set from the command line when the shader is compiled
+#define AA_PATTERN 0
+#define AA_FILTER 0
+#endif
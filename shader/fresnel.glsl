@@ -0,0 +1,18 @@
+// Splitting of the light at the surface of transparent materials
+
+// Schlick's approximation of the fraction of light reflected when going from n1 to n2, 1 on total internal reflection
+float schlick(float cos_i, float n1, float n2) {
+	float r0 = (n1 - n2) / (n1 + n2);
+	r0 *= r0;
+	float cos_x = cos_i;
+	if (n1 > n2) { // The transmitted angle is the largest one, use it instead
+		float eta = n1 / n2;
+		float sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+		if (sin2_t > 1.0) {
+			return 1.0;
+		}
+		cos_x = sqrt(1.0 - sin2_t);
+	}
+	float x = 1.0 - cos_x;
+	return r0 + (1.0 - r0) * x * x * x * x * x;
+}
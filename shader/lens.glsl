@@ -0,0 +1,50 @@
+// Thin lens camera, gives the depth of field
+
+// Uniform point on the lens, in the xy plane of the camera
+vec2 lens_sample(inout uint seed) {
+	float u = random(seed);
+	float v = random(seed);
+	if (pc.lens.blades < 3) { // Disk
+		float theta = 2.0 * PI * v;
+		return sqrt(u) * pc.lens.aperture * vec2(cos(theta), sin(theta));
+	}
+
+	// Uniform point in one of the triangles between the center and the sides of the polygon
+	float blade_angle = 2.0 * PI / float(pc.lens.blades);
+	float blade = floor(random(seed) * float(pc.lens.blades));
+	if (u + v > 1.0) { // Folds the square onto the triangle
+		u = 1.0 - u;
+		v = 1.0 - v;
+	}
+	float a = pc.lens.blade_rotation + blade * blade_angle;
+	vec2 corner_a = vec2(cos(a), sin(a));
+	vec2 corner_b = vec2(cos(a + blade_angle), sin(a + blade_angle));
+	return (u * corner_a + v * corner_b) * pc.lens.aperture;
+}
+
+// With autofocus, distance to the surface at the center of the screen, the focal distance of the lens when there is none
+float focal_distance() {
+	if (pc.lens.autofocus == 0) {
+		return pc.lens.focal_distance;
+	}
+
+	Ray r = Ray(pc.camera.pos, vec4(transform_point(pc.camera.orientation, vec3(0.0, 0.0, 1.0)), 0.0));
+	uint closest_si;
+	float closest_sphere_dist = Ray_trace_to_Spheres(r, closest_si);
+	uint closest_mi;
+	uint closest_tri_index;
+	vec2 uv;
+	float closest_model_dist = Ray_trace_to_Models(r, closest_mi, closest_tri_index, uv);
+
+	float dist = min(closest_sphere_dist, closest_model_dist);
+	return isinf(dist) ? pc.lens.focal_distance : dist;
+}
+
+// Moves the origin of a camera space ray to a random point of the lens, the ray still goes through the same point of the focal plane
+// The focal surface is a plane for the flat projections and a sphere around the camera for the others
+Ray lens_ray(Ray r, float focus, inout uint seed) {
+	float dist = pc.view.projection <= PROJECTION_ORTHOGRAPHIC ? focus / r.dir.z : focus;
+	vec3 focus_point = r.origin.xyz + r.dir.xyz * dist;
+	vec3 lens_point = r.origin.xyz + vec3(lens_sample(seed), 0.0);
+	return Ray(vec4(lens_point, 0.0), vec4(normalize(focus_point - lens_point), 0.0));
+}
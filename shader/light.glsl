@@ -86,7 +86,7 @@ vec3 PointLights_to_Model(vec4 impact_point, Model closest_m, Ray r, uint tri_in
 
 	// vec4 normal = vec4((normals[indices[tri_index][0]] + normals[indices[tri_index][1]] + normals[indices[tri_index][2]]) / 3.0, 0.0); // Average normal
 	
-	vec4 normal = get_normal(tri_index, uv); // UV interpolated normal for smoooooth shading
+	vec4 normal = get_normal(closest_m, tri_index, uv); // UV interpolated normal for smoooooth shading
 
 	// debugPrintfEXT("Cross n: (%f; %f; %f)", _normal.x, _normal.y, _normal.z);
 	// debugPrintfEXT("n: (%f; %f; %f)", normal.x, normal.y, normal.z);
@@ -102,12 +102,7 @@ vec3 PointLights_to_Model(vec4 impact_point, Model closest_m, Ray r, uint tri_in
 			to_light, // No need to normalize, because we don't need the exact distance if there is a collision
 		};
 
-		uint closest_mi;
-		uint _tri_index;
-		vec2 uv;
-		Ray_trace_to_Models(ray_to_light, closest_mi, _tri_index, uv);
-		
-		if (closest_mi == MODELS_LENGTH) { // Means no collisions detected, thus no shadow, so we need to compute the color for the current light
+		if (!Ray_hits_Models(ray_to_light)) { // Means no collisions detected, thus no shadow, so we need to compute the color for the current light
 			float to_light_sq_dist = dot(to_light, to_light);
 			
 			/*
@@ -144,12 +139,7 @@ vec3 PointLights_to_Model(vec4 impact_point, Model closest_m, Ray r, uint tri_in
 			to_light, // No need to normalize, because we don't need the exact distance if there is a collision
 		};
 
-		uint closest_mi;
-		uint _tri_index;
-		vec2 uv;
-		Ray_trace_to_Models(ray_to_light, closest_mi, _tri_index, uv);
-		
-		if (closest_mi == MODELS_LENGTH) { // Means no collisions detected, thus no shadow, so we need to compute the color for the current light
+		if (!Ray_hits_Models(ray_to_light)) { // Means no collisions detected, thus no shadow, so we need to compute the color for the current light
 			float to_light_sq_dist = dot(to_light, to_light);
 			
 			/*
@@ -51,24 +51,73 @@ float Ray_dist_to_Triangle(Ray r, vec3 A, vec3 B, vec3 C, out vec2 uv) {
 	return -1.0;
 }
 
-// Returns the dist to a model for a specific ray, returns -1.0 if no collision
-float Ray_dist_to_Model(Ray r, Model model, out uint closest_tri_index, out vec2 uv) {
+// Slab test, true if the ray enters the box before max_dist
+bool Ray_hits_Box(vec3 origin, vec3 inv_dir, vec3 box_min, vec3 box_max, float max_dist) {
+	vec3 t0 = (box_min - origin) * inv_dir;
+	vec3 t1 = (box_max - origin) * inv_dir;
+	vec3 t_near = min(t0, t1);
+	vec3 t_far = max(t0, t1);
+
+	float t_enter = max(max(t_near.x, t_near.y), t_near.z);
+	float t_exit = min(min(t_far.x, t_far.y), t_far.z);
+	return t_exit >= max(t_enter, 0.0) && t_enter < max_dist;
+}
+
+// Moves the ray into the object space of the model
+// The direction is not normalized, so a distance along the ray is the same in both spaces
+Ray Ray_to_object_space(Ray r, Model model) {
+	vec4 inv_rotation = conjugate(model.rotation);
+	vec3 origin = transform_point(inv_rotation, r.origin.xyz - model.pos.xyz) / model.scale.xyz;
+	vec3 dir = transform_point(inv_rotation, r.dir.xyz) / model.scale.xyz;
+	return Ray(vec4(origin, 0.0), vec4(dir, 0.0));
+}
+
+// Walks the BVH of the model, returns the dist to the closest triangle or -1.0 if no collision
+// With any_hit the traversal stops at the first collision, used for shadows
+float Ray_traverse_Model(Ray r, Model model, bool any_hit, out uint closest_tri_index, out vec2 uv) {
 	float closest_tri_dist = 1.0 / 0.0;
 	vec2 temp_uv = vec2(0.0);
-	vec3 pos = model.pos.xyz;
-
-	for (uint i = model.indices_start; i < model.indices_end; i++) {
-		uvec3 indexed_tri = indices[i];
-		vec3 A = vertices[indexed_tri.x] + pos;
-		vec3 B = vertices[indexed_tri.y] + pos;
-		vec3 C = vertices[indexed_tri.z] + pos;
-
-		float d = Ray_dist_to_Triangle(r, A, B, C, temp_uv);
-		if (d != -1.0 && d < closest_tri_dist) {
-			closest_tri_dist = d;
-			closest_tri_index = i;
-			uv = temp_uv;
-			// debugPrintfEXT("Base UV 2: (%f; %f)", uv.x, uv.y);
+
+	if (model.indices_start == model.indices_end) {
+		return -1.0;
+	}
+
+	// The BVH is built in object space, the transformed ray keeps the distances of the world space ray
+	Ray local_r = Ray_to_object_space(r, model);
+	vec3 origin = local_r.origin.xyz;
+	vec3 inv_dir = 1.0 / local_r.dir.xyz;
+
+	uint stack[BVH_STACK_SIZE];
+	uint stack_size = 0;
+	stack[stack_size++] = model.bvh_root;
+
+	while (stack_size > 0) {
+		BvhNode node = bvh_nodes[stack[--stack_size]];
+		if (!Ray_hits_Box(origin, inv_dir, node.min, node.max, closest_tri_dist)) {
+			continue;
+		}
+
+		if (node.count > 0) { // Leaf
+			for (uint i = node.left_first; i < node.left_first + node.count; i++) {
+				uvec3 indexed_tri = indices[i];
+				vec3 A = vertices[indexed_tri.x];
+				vec3 B = vertices[indexed_tri.y];
+				vec3 C = vertices[indexed_tri.z];
+
+				float d = Ray_dist_to_Triangle(local_r, A, B, C, temp_uv);
+				if (d != -1.0 && d < closest_tri_dist) {
+					closest_tri_dist = d;
+					closest_tri_index = i;
+					uv = temp_uv;
+
+					if (any_hit) {
+						return d;
+					}
+				}
+			}
+		} else {
+			stack[stack_size++] = node.left_first;
+			stack[stack_size++] = node.left_first + 1;
 		}
 	}
 
@@ -79,6 +128,25 @@ float Ray_dist_to_Model(Ray r, Model model, out uint closest_tri_index, out vec2
 	}
 }
 
+// Returns the dist to a model for a specific ray, returns -1.0 if no collision
+float Ray_dist_to_Model(Ray r, Model model, out uint closest_tri_index, out vec2 uv) {
+	return Ray_traverse_Model(r, model, false, closest_tri_index, uv);
+}
+
+// Returns true if the ray collides with any model, cheaper than Ray_trace_to_Models because it stops at the first collision
+bool Ray_hits_Models(Ray r) {
+	uint tri_index;
+	vec2 uv;
+
+	for (int i = 0; i < MODELS_LENGTH; i++) {
+		if (Ray_traverse_Model(r, models[i], true, tri_index, uv) != -1.0) {
+			return true;
+		}
+	}
+
+	return false;
+}
+
 // Traces the given ray to all spheres on the scene and returns the closest_d and writes to closest_mi the index of the detected model, uv is not interpolated
 // closest_mi == MODELS_LENGTH if no ray collisions
 float Ray_trace_to_Models(Ray r, out uint closest_mi, out uint closest_tri_index, out vec2 uv) {
@@ -104,12 +172,13 @@ float Ray_trace_to_Models(Ray r, out uint closest_mi, out uint closest_tri_index
 }
 
 vec3 Model_texture_value(Model m, vec2 uv) {
-    return texture(textures[m.texture_index], uv).xyz;
+    return texture(textures[materials[m.material_index].texture_index], uv).xyz;
 }
 
 vec3 get_color(Model m, uint tri_index, vec2 uv) {
-	if (m.texture_index == -1) {
-		return m.col.xyz;
+	Material mat = materials[m.material_index];
+	if (mat.texture_index == -1) {
+		return mat.col.xyz;
 	} else {
 		uvec3 indexed_tri = indices[tri_index];
 		vec2 tex_A = uvs[indexed_tri.x];
@@ -124,17 +193,21 @@ vec3 get_color(Model m, uint tri_index, vec2 uv) {
 	}
 }
 
-vec4 get_normal(uint tri_index, vec2 uv) {
-	
-	// vec3 n_A = normals[indices[tri_index][0]];
-	// vec3 n_B = normals[indices[tri_index][1]];
-	// vec3 n_C = normals[indices[tri_index][2]];
-	// return vec4(n_A + (n_B - n_A) * uv.x + (n_C - n_A) * uv.y, 0.0); // UV interpolated normal for smoooooth shading
-	
+vec4 get_normal(Model m, uint tri_index, vec2 uv) {
+	uvec3 indexed_tri = indices[tri_index];
+	vec3 object_normal;
+	if (m.smooth_shading != 0) { // UV interpolated normal for smooth shading
+		vec3 n_A = normals[indexed_tri.x];
+		vec3 n_B = normals[indexed_tri.y];
+		vec3 n_C = normals[indexed_tri.z];
+		object_normal = n_A + (n_B - n_A) * uv.x + (n_C - n_A) * uv.y;
+	} else { // Compute normal with the triangle
+		vec3 AB = vertices[indexed_tri.y] - vertices[indexed_tri.x]; // B - A
+		vec3 AC = vertices[indexed_tri.z] - vertices[indexed_tri.x]; // C - A
+		object_normal = cross(AB, AC);
+	}
 
-	// Compute normal with the triangle
-	vec3 AB = vertices[indices[tri_index][1]] - vertices[indices[tri_index][0]]; // B - A
-	vec3 AC = vertices[indices[tri_index][2]] - vertices[indices[tri_index][0]]; // C - A
-	vec4 normal = vec4(normalize(cross(AB, AC)), 0.0);
+	// Normals are transformed by the inverse transpose of rotation * scale, which is rotation * inverse(scale)
+	vec4 normal = vec4(normalize(transform_point(m.rotation, object_normal / m.scale.xyz)), 0.0);
 	return normal;
 }
\ No newline at end of file
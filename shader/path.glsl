@@ -0,0 +1,88 @@
+// Path tracer, follows one random path per ray, the frames are summed in the accumulation image
+
+// Direction in the hemisphere around n with a density proportional to the cosine, which cancels the cosine of diffuse surfaces
+vec3 cosine_sample(vec3 n, inout uint seed) {
+	float phi = 2.0 * PI * random(seed);
+	float r2 = random(seed);
+	float r = sqrt(r2);
+
+	vec3 t = normalize(cross(abs(n.x) > 0.5 ? vec3(0.0, 1.0, 0.0) : vec3(1.0, 0.0, 0.0), n));
+	vec3 b = cross(n, t);
+	return t * (cos(phi) * r) + b * (sin(phi) * r) + n * sqrt(1.0 - r2);
+}
+
+float max3(vec3 v) {
+	return max(max(v.x, v.y), v.z);
+}
+
+// Returns the light coming back along the ray
+// Surfaces split the light like in main, the path follows one of the diffuse, reflected and refracted directions picked at random
+vec3 trace_path(Ray r, inout uint seed) {
+	vec3 radiance = vec3(0.0);
+	vec3 throughput = vec3(1.0); // Fraction of the light coming back along the current ray which reaches the camera
+	int medium = -1;
+
+	for (uint bounce = 0; bounce < PATH_DEPTH; bounce++) {
+		Surface s;
+		if (!Ray_trace_to_Surface(r, s)) {
+			break;
+		}
+
+		if (medium != -1) { // Beer-Lambert absorption inside the medium
+			throughput *= exp(-materials[medium].absorption.xyz * s.dist);
+		}
+
+		Material _mat = materials[s.material_index];
+		radiance += throughput * (s.lights * s.c * _mat.diffuse_factor * _mat.opacity + _mat.emission.xyz); // Direct and emitted light
+
+		bool entering = dot(r.dir, s.normal) < 0.0;
+		vec4 facing_normal = entering ? s.normal : -s.normal;
+
+		float transparency = 1.0 - _mat.opacity;
+		float fresnel = 0.0;
+		vec3 refracted_dir = vec3(0.0);
+		if (transparency > 0.0) {
+			float n1 = medium == -1 ? 1.0 : materials[medium].ior;
+			float n2 = entering ? _mat.ior : 1.0;
+			fresnel = schlick(-dot(r.dir, facing_normal), n1, n2);
+			refracted_dir = refract(r.dir.xyz, facing_normal.xyz, n1 / n2);
+		}
+
+		vec3 diffuse_weight = s.c * _mat.diffuse_factor * _mat.opacity;
+		vec3 reflected_weight = s.c * _mat.reflexivity * _mat.opacity + transparency * fresnel;
+		vec3 refracted_weight = refracted_dir != vec3(0.0) ? vec3(transparency * (1.0 - fresnel)) : vec3(0.0);
+
+		// Each direction is picked with a probability proportional to its weight, the throughput is divided by that probability
+		float p_diffuse = max3(diffuse_weight);
+		float p_reflected = max3(reflected_weight);
+		float p_refracted = max3(refracted_weight);
+		float total = p_diffuse + p_reflected + p_refracted;
+		if (total <= 0.0) {
+			break;
+		}
+
+		float u = random(seed) * total;
+		if (u < p_diffuse || p_reflected + p_refracted == 0.0) {
+			throughput *= diffuse_weight * (total / p_diffuse);
+			r = Ray(s.point + facing_normal * RAY_COLLISION_PRECISION, vec4(cosine_sample(facing_normal.xyz, seed), 0.0));
+		} else if (u < p_diffuse + p_reflected || p_refracted == 0.0) {
+			throughput *= reflected_weight * (total / p_reflected);
+			r = Ray(s.point + facing_normal * RAY_COLLISION_PRECISION, reflect(r.dir, s.normal));
+		} else {
+			throughput *= refracted_weight * (total / p_refracted);
+			r = Ray(s.point - facing_normal * RAY_COLLISION_PRECISION, vec4(refracted_dir, 0.0));
+			medium = entering ? int(s.material_index) : -1;
+		}
+
+		// Russian roulette, paths carrying little light stop early and the surviving ones make up for them
+		if (bounce >= ROULETTE_BOUNCE) {
+			float survival = min(max3(throughput), 1.0);
+			if (random(seed) >= survival) {
+				break;
+			}
+			throughput /= survival;
+		}
+	}
+
+	return radiance;
+}
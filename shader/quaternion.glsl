@@ -14,8 +14,12 @@ vec4 multiply(vec4 quat, vec4 other) {
 	);
 }
 
+// Inverse rotation for unit quaternions
+vec4 conjugate(vec4 quat) {
+	return vec4(-quat.xyz, quat.w);
+}
+
 // Returns the given point rotated by the quaternion
 vec3 transform_point(vec4 quat, vec3 point) {
-	vec4 inv = vec4(-quat.xyz, quat.w);
-	return multiply(multiply(quat, vec4(point, 0.0)), inv).xyz;
+	return multiply(multiply(quat, vec4(point, 0.0)), conjugate(quat)).xyz;
 }
\ No newline at end of file
@@ -0,0 +1,14 @@
+// Random numbers for the path tracer and the anti-aliasing
+
+// PCG hash, good enough randomness from the pixel and sample indices
+uint pcg_hash(uint v) {
+	uint state = v * 747796405u + 2891336453u;
+	uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+	return (word >> 22u) ^ word;
+}
+
+// Uniform in [0, 1), 24 bits so the conversion to float is exact
+float random(inout uint seed) {
+	seed = pcg_hash(seed);
+	return float(seed >> 8u) / 16777216.0;
+}
@@ -11,25 +11,64 @@ struct Ray {
     vec4 dir;
 };
 
+struct Material {
+    vec4 col; // Albedo, used when there is no texture
+    vec4 emission; // Light emitted by the surface, added to the reflected light
+    vec4 absorption; // Beer-Lambert coefficients, fraction of the light absorbed per unit of distance travelled inside
+    int texture_index; // -1 for no texture
+    int normal_texture_index; // -1 for no normal map
+    float reflexivity; // When computing reflections, factor of the incoming light reflected
+    float diffuse_factor; // When computing reflections, factor of the added diffuse light to the incoming reflected light
+    float roughness;
+    float metallic;
+    float ior; // Index of refraction
+    float opacity; // The rest of the light is split between reflection and refraction by the Fresnel equations
+};
+
 struct Sphere {
     vec4 pos;
-    vec4 col;
+    vec4 orientation; // Quaternion, rotates the texture around the center
     float r;
-    float reflexivity; // When computing reflections, factor of the incoming light reflected
-    float diffuse_factor; // When computing reflections, factor of the added diffuse light to the incoming reflected light
-    int texture_index;
+    uint material_index;
 };
 
 struct Model {
     vec4 pos;
-    vec4 col;
-    float reflexivity; // When computing reflections, factor of the incoming light reflected
-    float diffuse_factor; // When computing reflections, factor of the added diffuse light to the incoming reflected light
+    vec4 rotation; // Quaternion, from object space to world space
+    vec4 scale; // Applied in object space before the rotation
     uint indices_start; // Index of the first indexed triangle of the model in the global indexed triangles array
     uint indices_end; // End of the indexed triangles
     uint vertex_start;
     uint vertex_end;
-    int texture_index;
+    uint bvh_root; // Index of the root node of the model in bvh_nodes
+    uint material_index;
+    uint smooth_shading; // 1 to interpolate the vertex normals, 0 to use the normal of the triangle
+};
+
+struct BvhNode {
+    vec3 min;
+    uint left_first; // Interior nodes: index of the left child, the right child follows it. Leaves: index of the first triangle
+    vec3 max;
+    uint count; // Number of triangles in a leaf, 0 for interior nodes
+};
+
+// Ray waiting to be traced, rays split in two at transparent surfaces
+struct PendingRay {
+    Ray r;
+    vec3 weight; // Fraction of the light coming back along the ray which reaches the camera
+    uint depth; // Number of surfaces hit before this ray
+    int medium; // Index of the material the ray travels through, -1 for the air
+    bool reflected; // Reflected light fades with the distance to the surface it comes from
+};
+
+// Closest surface hit by a ray
+struct Surface {
+    float dist;
+    vec4 point;
+    vec4 normal;
+    vec3 c; // Color of the material or its texture at the point
+    vec3 lights; // Light received directly from the light sources
+    uint material_index;
 };
 
 struct PointLight {
@@ -44,48 +83,76 @@ struct DirectionalLight {
     float intensity;
 };
 
-layout(set = 0, binding = 1, std430) buffer Rays {
-    Ray rays[];
-};
-
-layout(set = 0, binding = 2, std430) buffer Spheres {
+layout(set = 0, binding = 1, std430) buffer Spheres {
     Sphere spheres[];
 };
 
-layout(set = 0, binding = 3, std430) buffer Models {
+layout(set = 0, binding = 2, std430) buffer Models {
     Model models[];
 };
 
-layout(set = 0, binding = 4, std430) buffer Vertices {
+layout(set = 0, binding = 3, std430) buffer Vertices {
     vec3 vertices[];
 };
 
-layout(set = 0, binding = 5, std430) buffer UVs {
+layout(set = 0, binding = 4, std430) buffer UVs {
     vec2 uvs[];
 };
 
-layout(set = 0, binding = 6, std430) buffer Indices {
+layout(set = 0, binding = 5, std430) buffer Indices {
     uvec3 indices[];
 };
 
-layout(set = 0, binding = 7, std430) buffer Normals {
+layout(set = 0, binding = 6, std430) buffer Normals {
     vec3 normals[];
 };
 
-layout(set = 0, binding = 8, std430) buffer PointLights {
+layout(set = 0, binding = 7, std430) buffer PointLights {
     PointLight point_lights[];
 };
 
-layout(set = 0, binding = 9, std430) buffer DirectionalLights {
+layout(set = 0, binding = 8, std430) buffer DirectionalLights {
     DirectionalLight directional_lights[];
 };
 
-layout(set = 0, binding = 10) uniform sampler2D textures[2];
+layout(set = 0, binding = 9) uniform sampler2D textures[TEXTURE_COUNT];
+
+layout(set = 0, binding = 10, std430) buffer Bvh {
+    BvhNode bvh_nodes[];
+};
+
+layout(set = 0, binding = 11, std430) buffer Materials {
+    Material materials[];
+};
+
+// Sum of the samples of the previous frames weighted by the anti-aliasing filter, the alpha is the sum of the weights
+layout(set = 0, binding = 12, rgba32f) uniform image2D accumulation;
 
-layout(push_constant) uniform Camera {
+struct Camera {
     vec4 pos;
     vec4 orientation; // Quaternion
-} camera;
+};
+
+struct Lens {
+    float aperture; // Radius of the lens, 0 for a pinhole camera
+    float focal_distance; // Distance from the camera to the plane in focus
+    uint blades; // Sides of the lens, a disk below 3
+    float blade_rotation;
+    uint autofocus; // 1 to focus on the surface at the center of the screen
+};
+
+struct View {
+    uint projection; // PROJECTION_ constants of camera.glsl
+    float fov; // Horizontal field of view, in radians
+    float ortho_width; // Width of the orthographic view, in scene units
+};
+
+layout(push_constant) uniform PushConstants {
+    Camera camera;
+    Lens lens;
+    View view;
+    uint sample_index; // Frames already in the accumulation image, 0 restarts the accumulation
+} pc;
 
 uint SPHERES_LENGTH = spheres.length();
 uint MODELS_LENGTH = models.length();
@@ -96,16 +163,51 @@ uint DIR_LIGHTS_COUNT = directional_lights.length();
 #include "sphere.glsl"
 #include "model.glsl"
 #include "light.glsl"
+#include "fresnel.glsl"
+#include "random.glsl"
 
-void main() {
-    ivec2 img_size = imageSize(img);
-    uint ri = gl_GlobalInvocationID.y * img_size.x + gl_GlobalInvocationID.x;
-    Ray r = rays[ri];
+// Returns false when the ray goes to infinity
+bool Ray_trace_to_Surface(Ray r, out Surface s) {
+    uint closest_si;
+    float closest_sphere_dist = Ray_trace_to_Spheres(r, closest_si);
+
+    uint closest_mi;
+    uint closest_tri_index;
+    vec2 uv;
+    float closest_model_dist = Ray_trace_to_Models(r, closest_mi, closest_tri_index, uv);
 
-    r.origin += camera.pos;
-    r.dir.xyz = transform_point(camera.orientation, r.dir.xyz);
+    if (closest_si == SPHERES_LENGTH && closest_mi == MODELS_LENGTH) {
+        return false;
+    }
+
+    if (closest_sphere_dist < closest_model_dist) {
+        Sphere _sph = spheres[closest_si];
+        s.dist = closest_sphere_dist;
+        s.point = r.origin + r.dir * s.dist;
+        s.normal = get_normal(_sph, s.point);
+        s.c = get_color(_sph, s.point);
+        s.lights = PointLights_to_Sphere(s.point, _sph, r);
+        s.material_index = _sph.material_index;
+    } else {
+        Model _mod = models[closest_mi];
+        s.dist = closest_model_dist;
+        s.point = r.origin + r.dir * s.dist;
+        s.normal = get_normal(_mod, closest_tri_index, uv);
+        s.c = get_color(_mod, closest_tri_index, uv);
+        s.lights = PointLights_to_Model(s.point, _mod, r, closest_tri_index, uv);
+        s.material_index = _mod.material_index;
+    }
+    return true;
+}
+
+#include "path.glsl"
+#include "antialiasing.glsl"
+#include "camera.glsl"
+#include "lens.glsl"
 
-    vec4 col = vec4(0.0, 0.0, 0.0, 1.0);
+// Direct lighting and mirror reflections, returns the light coming back along the ray
+vec3 trace_ray(Ray r) {
+    vec3 col = vec3(0.0);
 
     /*
     if (closest_si != SPHERES_LENGTH) {
@@ -172,116 +274,56 @@ void main() {
     }
     */
 
-    uint closest_si;
-    float closest_sphere_dist = Ray_trace_to_Spheres(r, closest_si);
-
-    uint closest_mi;
-    uint closest_tri_index;
-    vec2 uv;
-    float closest_model_dist = Ray_trace_to_Models(r, closest_mi, closest_tri_index, uv);
+    PendingRay stack[RAY_STACK_SIZE];
+    stack[0] = PendingRay(r, vec3(1.0), 0, -1, false);
+    uint stack_len = 1;
 
-    if (closest_si != SPHERES_LENGTH || closest_mi != MODELS_LENGTH) {
-        vec4 impact_points[REFLECT_DEPTH];
-        uint impact_sindices[REFLECT_DEPTH];
-        uint impact_mindices[REFLECT_DEPTH];
-        float impact_distances[REFLECT_DEPTH];
-        
-        uint tri_indices[REFLECT_DEPTH]; // Stores the triangle index for each reflection
-        vec2 current_uvs[REFLECT_DEPTH];
+    while (stack_len > 0) {
+        PendingRay p = stack[--stack_len];
+        r = p.r;
 
-        float closest_dist = 0;
-        vec4 normal;
-
-        if (closest_sphere_dist < closest_model_dist) {
-            closest_dist = closest_sphere_dist;
-            impact_sindices[0] = closest_si;
-            impact_mindices[0] = MODELS_LENGTH; // Invalidates this index for the Models
-            impact_points[0] = r.origin + r.dir * closest_dist;
-            normal = get_normal(spheres[impact_sindices[0]], impact_points[0]);
-            col.xyz = get_color(spheres[impact_mindices[0]], impact_points[0]);
-        } else {
-            closest_dist = closest_model_dist;
-            normal = get_normal(closest_tri_index, uv);
-            impact_mindices[0] = closest_mi;
-            impact_sindices[0] = SPHERES_LENGTH; // Invalidates this index for the Spheres
-            tri_indices[0] = closest_tri_index;
-            current_uvs[0] = uv;
-            impact_points[0] = r.origin + r.dir * closest_dist;
-            col.xyz = get_color(models[closest_mi], closest_tri_index, uv);
+        Surface s;
+        if (!Ray_trace_to_Surface(r, s)) { // The ray goes to infinity
+            continue;
         }
 
-        impact_distances[0] = closest_dist; // We choose arbitrarily to ignore the camera
-
-        r.origin = impact_points[0] + normal * RAY_COLLISION_PRECISION;
-        r.dir = reflect(r.dir, normal); // Used to iterate through reflections
-
-        int i; // So we can keep track of when the for loop stopped for later
-        for (i = 1; i < REFLECT_DEPTH; i++) {
-            closest_sphere_dist = Ray_trace_to_Spheres(r, closest_si);
-            closest_model_dist = Ray_trace_to_Models(r, closest_mi, closest_tri_index, uv);
-
-            if (closest_si != SPHERES_LENGTH || closest_mi != MODELS_LENGTH) {
-                if (closest_sphere_dist < closest_model_dist) {
-                    closest_dist = closest_sphere_dist;
-                    normal = get_normal(spheres[closest_si], impact_points[i]);
-                    impact_sindices[i] = closest_si;
-                    impact_mindices[i] = MODELS_LENGTH; // Invalidates this index for the spheres
-                } else {
-                    closest_dist = closest_model_dist;
-                    normal = get_normal(closest_tri_index, uv);
-                    impact_mindices[i] = closest_mi;
-                    tri_indices[i] = closest_tri_index;
-                    current_uvs[i] = uv;
-                    impact_sindices[i] = SPHERES_LENGTH; // Invalidates this index for the models
-                }
-
-                impact_points[i] = r.origin + r.dir * closest_dist;
-                
-                r.origin = impact_points[i] + normal * RAY_COLLISION_PRECISION; // New origin is the impact point
-                r.dir = reflect(r.dir, normal); // Used to iterate through reflections
-
-            } else {
-                break;
-            }
+        if (p.medium != -1) { // Beer-Lambert absorption inside the medium
+            p.weight *= exp(-materials[p.medium].absorption.xyz * s.dist);
+        }
+        if (p.reflected) {
+            p.weight *= min(1.0, 1 / (s.dist * s.dist));
         }
 
-        if (i > 0) {
-            --i; // or index out of range
+        Material _mat = materials[s.material_index];
+        col += p.weight * (s.lights * s.c * _mat.diffuse_factor * _mat.opacity + _mat.emission.xyz); // Add diffused and emitted light
 
-            // Determinate using impact_sindices and impact_mindices which of the two to use for the base reflected color
-            vec3 reflected_color;
-            if (impact_sindices[i] == SPHERES_LENGTH) { // Spheres unvalidated, take color from models
-                Model _mod = models[impact_mindices[i]];
-                vec3 c = get_color(_mod, tri_indices[i], current_uvs[i]);
-                reflected_color = PointLights_to_Model(impact_points[i], _mod, r, tri_indices[i], current_uvs[i]) * c * _mod.diffuse_factor;
-            } else { // Models unvalidated, take color from spheres
-                Sphere _sph = spheres[impact_sindices[i]];
-                reflected_color = PointLights_to_Sphere(impact_points[i], _sph, r) * get_color(_sph, impact_points[i]) * _sph.diffuse_factor;
-            }
-
-            for (int a = i - 1; a >= 0; a--) {
-                vec3 added_diffuse_color;
-                if (impact_sindices[a] == SPHERES_LENGTH) { // Spheres unvalidated, take color from models
-                    Model _mod = models[impact_mindices[a]];
-                    vec3 c = get_color(_mod, tri_indices[a], current_uvs[a]);
-                    reflected_color *= c * _mod.reflexivity;
-                    added_diffuse_color = PointLights_to_Model(impact_points[a], _mod, r, tri_indices[a], current_uvs[a]) * c * _mod.diffuse_factor; // Add diffused light
-                } else { // Models unvalidated, take color from spheres
-                    Sphere _sph = spheres[impact_sindices[a]];
-                    vec3 c = get_color(_sph, impact_points[a]);
-                    reflected_color *= c * _sph.reflexivity;
-                    added_diffuse_color = (PointLights_to_Sphere(impact_points[a], _sph, r) * c * _sph.diffuse_factor); // Add diffused light
-                }
+        if (p.depth + 1 >= REFLECT_DEPTH) {
+            continue;
+        }
 
-                float impact_dist = impact_distances[a + 1];
-                float df = min(1.0, 1 / (impact_dist * impact_dist));
-                reflected_color *= df;
-                reflected_color += added_diffuse_color;
+        bool entering = dot(r.dir, s.normal) < 0.0;
+        vec4 facing_normal = entering ? s.normal : -s.normal;
+
+        // Transparent surfaces split the light between reflection and refraction, the opaque part only reflects
+        float transparency = 1.0 - _mat.opacity;
+        float fresnel = 0.0;
+        if (transparency > 0.0) {
+            float n1 = p.medium == -1 ? 1.0 : materials[p.medium].ior;
+            float n2 = entering ? _mat.ior : 1.0; // Rays leaving an object go back to the air
+            fresnel = schlick(-dot(r.dir, facing_normal), n1, n2);
+
+            vec3 refracted_dir = refract(r.dir.xyz, facing_normal.xyz, n1 / n2);
+            vec3 refracted_weight = p.weight * transparency * (1.0 - fresnel);
+            if (refracted_dir != vec3(0.0) && any(greaterThan(refracted_weight, vec3(0.0)))) { // refract returns 0 on total internal reflection
+                Ray refracted = Ray(s.point - facing_normal * RAY_COLLISION_PRECISION, vec4(refracted_dir, 0.0));
+                stack[stack_len++] = PendingRay(refracted, refracted_weight, p.depth + 1, entering ? int(s.material_index) : -1, false);
             }
+        }
 
-            // float last_df = 1 / (impact_distances[0] * impact_distances[0]);
-            col = vec4(reflected_color, 1.0);
-            // col = texture(textures[0], vec2(0.05, 0.05));
+        vec3 reflected_weight = p.weight * (s.c * _mat.reflexivity * _mat.opacity + transparency * fresnel);
+        if (any(greaterThan(reflected_weight, vec3(0.0)))) {
+            Ray reflected = Ray(s.point + facing_normal * RAY_COLLISION_PRECISION, reflect(r.dir, s.normal));
+            stack[stack_len++] = PendingRay(reflected, reflected_weight, p.depth + 1, p.medium, true);
         }
     }
 
@@ -374,7 +416,60 @@ void main() {
         }
     }
     */
-    
-    imageStore(img, ivec2(gl_GlobalInvocationID.xy), col);
+
+    return col;
+}
+
+void main() {
+    ivec2 img_size = imageSize(img);
+    if (gl_GlobalInvocationID.x >= img_size.x || gl_GlobalInvocationID.y >= img_size.y) { // The dispatch size is rounded up
+        return;
+    }
+
+    uint ri = gl_GlobalInvocationID.y * img_size.x + gl_GlobalInvocationID.x;
+    ivec2 pixel = ivec2(gl_GlobalInvocationID.xy);
+
+    float focus = pc.lens.aperture > 0.0 ? focal_distance() : 0.0;
+
+    vec4 sum = vec4(0.0); // Colors weighted by the filter, the alpha is the sum of the weights
+    for (uint k = 0; k < AA_SAMPLES; k++) {
+        uint sample_id = pc.sample_index * AA_SAMPLES + k;
+        uint seed = pcg_hash(ri + pcg_hash(sample_id));
+
+        vec2 offset = vec2(0.0); // From the corner of the pixel
+        float weight = 1.0;
+#ifdef JITTER
+        vec2 jitter = (sample_position(pixel, sample_id, k, seed) - 0.5) * 2.0 * FILTER_RADIUS; // From the center of the pixel
+        offset = 0.5 + jitter;
+        weight = filter_weight(jitter);
+#endif
+        Ray r;
+        if (!primary_ray(pixel, img_size, offset, r)) { // Outside of the fisheye circle
+            sum.a += weight;
+            continue;
+        }
+        if (pc.lens.aperture > 0.0) {
+            r = lens_ray(r, focus, seed);
+        }
+
+        r.origin.xyz = transform_point(pc.camera.orientation, r.origin.xyz) + pc.camera.pos.xyz;
+        r.dir.xyz = transform_point(pc.camera.orientation, r.dir.xyz);
+
+#ifdef PATH_TRACING
+        sum += vec4(trace_path(r, seed) * weight, weight);
+#else
+        sum += vec4(trace_ray(r) * weight, weight);
+#endif
+    }
+
+#ifdef ACCUMULATE
+    if (pc.sample_index > 0) { // Frames accumulated since the view last changed
+        sum += imageLoad(accumulation, pixel);
+    }
+    imageStore(accumulation, pixel, sum);
+#endif
+
+    vec4 col = vec4(sum.a > 0.0 ? sum.xyz / sum.a : vec3(0.0), 1.0);
+    imageStore(img, pixel, col);
 }
 
@@ -41,7 +41,7 @@ float Ray_trace_to_Spheres(Ray r, out uint closest_si) {
 }
 
 vec2 point_to_geo(vec4 point, Sphere s) {
-    vec3 sphereNormal = normalize(point - s.pos).xyz;
+    vec3 sphereNormal = transform_point(conjugate(s.orientation), normalize(point - s.pos).xyz); // Texture space of the sphere
             
     vec3 northVector = vec3(0, 1, 0);
     vec3 eastVector  = vec3(1, 0, 0);
@@ -65,12 +65,13 @@ vec2 point_to_geo(vec4 point, Sphere s) {
 
 vec3 get_color(Sphere s, vec4 impact_point) {
     // Computing U, V coordinates for the sphere, https://en.wikipedia.org/wiki/UV_mapping
-    if (s.texture_index != -1) {
+    Material mat = materials[s.material_index];
+    if (mat.texture_index != -1) {
         vec2 uv = point_to_geo(impact_point, s);
 
-        return texture(textures[s.texture_index], uv).xyz;
+        return texture(textures[mat.texture_index], uv).xyz;
     }
-    return s.col.xyz;
+    return mat.col.xyz;
 }
 
 vec4 get_normal(Sphere s, vec4 impact_point) {
@@ -0,0 +1,598 @@
+// Scene animation, tracks driving the properties of the objects, lights, materials and camera from the time t
+// Every track starts from a base value: the value of the scene file for the objects, lights and materials, the controlled camera for the camera
+// Keyframes replace the base value, the procedural drivers move it, and values are computed from t so they don't drift
+
+use std::f32::consts::TAU;
+use std::ops::Range;
+use std::path::Path;
+
+use nalgebra_glm::{Vec3, Vec4};
+use serde::Deserialize;
+
+use crate::camera::Camera;
+use crate::geom::{sphere::Sphere, model::Model};
+use crate::light::{PointLight, DirectionalLight};
+use crate::material::Material;
+use crate::quaternion::Quaternion;
+
+/// Object animated by a track
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Target {
+	Sphere(usize), // Index in the `spheres` of the scene
+	Model(usize), // Index in the `models` of the scene, every sub mesh of the file moves with it
+	PointLight(usize),
+	DirectionalLight(usize),
+	Material(usize), // Declared material, the objects overriding some of its properties have their own copy and don't change
+	Camera,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Property {
+	Pos, // Spheres, models, point lights and camera
+	Rotation, // Spheres, models and camera, degrees around the x, y and z axes like `ModelDesc::rotation`
+	Scale, // Models
+	Radius, // Spheres
+	Dir, // Directional lights
+	Col, // Lights, 3 components, and materials, 4 components
+	Intensity, // Lights
+	Emission, // Materials
+	Reflexivity,
+	DiffuseFactor,
+	Roughness,
+	Metallic,
+	Opacity,
+	Ior,
+	Fov, // Camera, degrees
+}
+
+/// Curve followed from a key to the next one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum Easing {
+	#[default]
+	Linear,
+	Step, // Holds the value of the key until the next one
+	EaseIn, // Cubic, starts slowly
+	EaseOut, // Cubic, ends slowly
+	EaseInOut,
+}
+
+impl Easing {
+	/// Eased fraction of the way between two keys
+	pub fn apply(self, h: f32) -> f32 {
+		match self {
+			Easing::Linear => h,
+			Easing::Step => 0.0,
+			Easing::EaseIn => h * h * h,
+			Easing::EaseOut => 1.0 - (1.0 - h).powi(3),
+			Easing::EaseInOut => if h < 0.5 { 4.0 * h * h * h } else { 1.0 - (2.0 - 2.0 * h).powi(3) / 2.0 }
+		}
+	}
+}
+
+/// What keyframes do after the last key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum Repeat {
+	#[default]
+	Once, // Holds the value of the first key before it and the value of the last key after it
+	Loop,
+	PingPong, // Plays forward then backward
+}
+
+/// Value of a property, written like the fields of the scene: `2.0` or `(0.0, 1.0, 0.0)`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(untagged)]
+pub enum ValueDesc {
+	Scalar(f32),
+	Vec3([f32; 3]),
+	Vec4([f32; 4]),
+}
+
+impl ValueDesc {
+	pub fn components(&self) -> &[f32] {
+		match self {
+			ValueDesc::Scalar(v) => std::slice::from_ref(v),
+			ValueDesc::Vec3(v) => v,
+			ValueDesc::Vec4(v) => v
+		}
+	}
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyDesc {
+	pub time: f32, // Seconds
+	pub value: ValueDesc, // Same number of components as the property
+	#[serde(default)]
+	pub easing: Easing, // Curve to the next key
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum DriverDesc {
+	Keyframes {
+		keys: Vec<KeyDesc>, // Sorted by time
+		#[serde(default)]
+		repeat: Repeat,
+	},
+	// Turns a position around the axis going through center, degrees per second
+	Orbit {
+		#[serde(default)]
+		center: [f32; 3],
+		axis: [f32; 3],
+		speed: f32,
+	},
+	// Adds amplitude * sin(2 pi (t / period + phase)) to the value, a rotation oscillates by the amplitude in degrees
+	Oscillate {
+		amplitude: ValueDesc,
+		period: f32, // Seconds
+		#[serde(default)]
+		phase: f32, // Fraction of a period
+	},
+	// Turns a rotation or a direction around the axis, degrees per second
+	Spin {
+		axis: [f32; 3],
+		speed: f32,
+	},
+}
+
+/// Written like `(target: Sphere(0), property: Pos, driver: Orbit(axis: (0.0, 1.0, 0.0), speed: 30.0))` in the `animations` of a scene
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrackDesc {
+	pub target: Target,
+	pub property: Property,
+	pub driver: DriverDesc,
+}
+
+/// See `Animation::stale_buffers`
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct StaleBuffers {
+	pub spheres: bool,
+	pub models: bool,
+	pub lights: bool,
+	pub materials: bool,
+}
+
+// Target with the models of the file expanded to their sub meshes
+#[derive(Debug, Clone, PartialEq)]
+enum Object {
+	Sphere(usize),
+	Models(Range<usize>),
+	PointLight(usize),
+	DirectionalLight(usize),
+	Material(usize),
+	Camera,
+}
+
+#[derive(Debug, Clone)]
+struct Key {
+	time: f32,
+	value: Vec4, // Quaternion for a rotation
+	easing: Easing,
+}
+
+#[derive(Debug, Clone)]
+enum Driver {
+	Keyframes { keys: Vec<Key>, repeat: Repeat },
+	Orbit { center: Vec3, axis: Vec3, speed: f32 }, // Radians per second
+	Oscillate { amplitude: Vec4, period: f32, phase: f32 }, // Radians for a rotation
+	Spin { axis: Vec3, speed: f32 },
+}
+
+#[derive(Debug, Clone)]
+struct Track {
+	object: Object,
+	property: Property,
+	driver: Driver,
+	base: Vec4, // Value of the scene file, unused by the camera
+}
+
+impl Track {
+	fn value_at(&self, t: f32, base: Vec4) -> Vec4 {
+		let rotation = self.property == Property::Rotation;
+		match &self.driver {
+			Driver::Keyframes { keys, repeat } => {
+				let time = repeat_time(keys, *repeat, t);
+				let last = keys.len() - 1;
+				if time <= keys[0].time {
+					return keys[0].value;
+				}
+				if time >= keys[last].time {
+					return keys[last].value;
+				}
+
+				let i = keys.iter().rposition(|k| k.time <= time).unwrap_or(0).min(last - 1);
+				let h = keys[i].easing.apply((time - keys[i].time) / (keys[i + 1].time - keys[i].time));
+				let (a, b) = (keys[i].value, keys[i + 1].value);
+				match self.property {
+					Property::Rotation => from_quaternion(quaternion(a).slerp(&quaternion(b), h)),
+					Property::Dir => a.lerp(&b, h).normalize(), // Keys are normalized and never opposite, see `Animation::add`
+					_ => a.lerp(&b, h)
+				}
+			},
+			Driver::Orbit { center, axis, speed } => {
+				let pos = Quaternion::from_axis(*axis, speed * t).transform_around(base.xyz(), *center);
+				Vec4::new(pos.x, pos.y, pos.z, base.w)
+			},
+			Driver::Oscillate { amplitude, period, phase } => {
+				let offset = amplitude * (TAU * (t / period + phase)).sin();
+				if rotation {
+					from_quaternion(Quaternion::from_euler(offset.x, offset.y, offset.z) * quaternion(base))
+				} else {
+					base + offset
+				}
+			},
+			Driver::Spin { axis, speed } => {
+				let spin = Quaternion::from_axis(*axis, speed * t);
+				if rotation {
+					from_quaternion(spin * quaternion(base))
+				} else {
+					let dir = spin.transform_point(base.xyz());
+					Vec4::new(dir.x, dir.y, dir.z, base.w)
+				}
+			}
+		}
+	}
+}
+
+// Time inside the keys after applying the repeat mode
+fn repeat_time(keys: &[Key], repeat: Repeat, t: f32) -> f32 {
+	let start = keys[0].time;
+	let duration = keys[keys.len() - 1].time - start;
+	if duration <= 0.0 {
+		return start;
+	}
+	match repeat {
+		Repeat::Once => t,
+		Repeat::Loop => start + (t - start).rem_euclid(duration),
+		Repeat::PingPong => {
+			let u = (t - start).rem_euclid(2.0 * duration);
+			start + if u > duration { 2.0 * duration - u } else { u }
+		}
+	}
+}
+
+fn quaternion(v: Vec4) -> Quaternion {
+	Quaternion::new(v.x, v.y, v.z, v.w)
+}
+
+fn from_quaternion(q: Quaternion) -> Vec4 {
+	Vec4::from(<[f32; 4]>::from(q))
+}
+
+/// Buffers written by the animation, the shader buffers mapped on the host or the vectors of the scene
+pub struct SceneBuffers<'a> {
+	pub spheres: &'a mut [Sphere],
+	pub models: &'a mut [Model],
+	pub point_lights: &'a mut [PointLight],
+	pub directional_lights: &'a mut [DirectionalLight],
+	pub materials: &'a mut [Material],
+}
+
+// Number of components of a property of an object, None when the object doesn't have it
+fn components(object: &Object, property: Property) -> Option<usize> {
+	use Property::*;
+	match (object, property) {
+		(Object::Sphere(_), Pos | Rotation) | (Object::Models(_), Pos | Rotation | Scale) | (Object::PointLight(_), Pos | Col)
+			| (Object::DirectionalLight(_), Dir | Col) | (Object::Material(_), Emission) | (Object::Camera, Pos | Rotation) => Some(3),
+		(Object::Sphere(_), Radius) | (Object::PointLight(_) | Object::DirectionalLight(_), Intensity)
+			| (Object::Material(_), Reflexivity | DiffuseFactor | Roughness | Metallic | Opacity | Ior) | (Object::Camera, Fov) => Some(1),
+		(Object::Material(_), Col) => Some(4),
+		_ => None
+	}
+}
+
+fn vec3(v: Vec4) -> [f32; 3] {
+	[v.x, v.y, v.z]
+}
+
+fn vec4(v: [f32; 3], w: f32) -> Vec4 {
+	Vec4::new(v[0], v[1], v[2], w)
+}
+
+fn scalar(v: f32) -> Vec4 {
+	Vec4::new(v, 0.0, 0.0, 0.0)
+}
+
+// Reads the property of an object, rotations as quaternions
+fn get(object: &Object, property: Property, b: &SceneBuffers) -> Vec4 {
+	use Property::*;
+	match (object, property) {
+		(Object::Sphere(i), Pos) => Vec4::from(b.spheres[*i].pos),
+		(Object::Sphere(i), Rotation) => Vec4::from(b.spheres[*i].orientation),
+		(Object::Sphere(i), Radius) => scalar(b.spheres[*i].r),
+		(Object::Models(r), Pos) => Vec4::from(b.models[r.start].pos),
+		(Object::Models(r), Rotation) => Vec4::from(b.models[r.start].rotation),
+		(Object::Models(r), Scale) => Vec4::from(b.models[r.start].scale),
+		(Object::PointLight(i), Pos) => Vec4::from(b.point_lights[*i].pos),
+		(Object::PointLight(i), Col) => vec4(b.point_lights[*i].col, 0.0),
+		(Object::PointLight(i), Intensity) => scalar(b.point_lights[*i].intensity),
+		(Object::DirectionalLight(i), Dir) => Vec4::from(b.directional_lights[*i].direction),
+		(Object::DirectionalLight(i), Col) => vec4(b.directional_lights[*i].col, 0.0),
+		(Object::DirectionalLight(i), Intensity) => scalar(b.directional_lights[*i].intensity),
+		(Object::Material(i), Col) => Vec4::from(b.materials[*i].col),
+		(Object::Material(i), Emission) => Vec4::from(b.materials[*i].emission),
+		(Object::Material(i), Reflexivity) => scalar(b.materials[*i].reflexivity),
+		(Object::Material(i), DiffuseFactor) => scalar(b.materials[*i].diffuse_factor),
+		(Object::Material(i), Roughness) => scalar(b.materials[*i].roughness),
+		(Object::Material(i), Metallic) => scalar(b.materials[*i].metallic),
+		(Object::Material(i), Opacity) => scalar(b.materials[*i].opacity),
+		(Object::Material(i), Ior) => scalar(b.materials[*i].ior),
+		_ => Vec4::zeros() // The camera and the properties rejected by `components`
+	}
+}
+
+fn set(object: &Object, property: Property, v: Vec4, b: &mut SceneBuffers) {
+	use Property::*;
+	match (object, property) {
+		(Object::Sphere(i), Pos) => b.spheres[*i].pos = v.into(),
+		(Object::Sphere(i), Rotation) => b.spheres[*i].orientation = v.into(),
+		(Object::Sphere(i), Radius) => b.spheres[*i].r = v.x.max(0.0),
+		(Object::Models(r), Pos) => b.models[r.clone()].iter_mut().for_each(|m| m.pos = v.into()),
+		(Object::Models(r), Rotation) => b.models[r.clone()].iter_mut().for_each(|m| m.rotation = v.into()),
+		(Object::Models(r), Scale) => b.models[r.clone()].iter_mut().for_each(|m| m.scale = v.into()),
+		(Object::PointLight(i), Pos) => b.point_lights[*i].pos = v.into(),
+		(Object::PointLight(i), Col) => b.point_lights[*i].col = vec3(v),
+		(Object::PointLight(i), Intensity) => b.point_lights[*i].intensity = v.x,
+		(Object::DirectionalLight(i), Dir) => b.directional_lights[*i].direction = v.into(),
+		(Object::DirectionalLight(i), Col) => b.directional_lights[*i].col = vec3(v),
+		(Object::DirectionalLight(i), Intensity) => b.directional_lights[*i].intensity = v.x,
+		(Object::Material(i), Col) => b.materials[*i].col = v.into(),
+		(Object::Material(i), Emission) => b.materials[*i].emission = v.into(),
+		(Object::Material(i), Reflexivity) => b.materials[*i].reflexivity = v.x,
+		(Object::Material(i), DiffuseFactor) => b.materials[*i].diffuse_factor = v.x,
+		(Object::Material(i), Roughness) => b.materials[*i].roughness = v.x,
+		(Object::Material(i), Metallic) => b.materials[*i].metallic = v.x,
+		(Object::Material(i), Opacity) => b.materials[*i].opacity = v.x.clamp(0.0, 1.0), // Checked like the scene file
+		(Object::Material(i), Ior) => b.materials[*i].ior = v.x.max(1e-3),
+		_ => ()
+	}
+}
+
+/// Tracks of a scene
+#[derive(Debug, Clone, Default)]
+pub struct Animation {
+	tracks: Vec<Track>,
+	models: Vec<Range<usize>>, // Sub meshes of each model of the scene file
+	declared_materials: usize,
+}
+
+impl Animation {
+	/// `models` are the sub meshes of each model of the scene file, the materials declared by the scene come first in the buffers
+	pub fn new(models: Vec<Range<usize>>, declared_materials: usize) -> Self {
+		Self {
+			tracks: Vec::new(),
+			models,
+			declared_materials
+		}
+	}
+
+	/// Checks the track against the scene and adds it, errors are prefixed with `entry` like the errors of the scene
+	pub fn add(&mut self, desc: &TrackDesc, buffers: &SceneBuffers, entry: &str, origin: &Path) -> Result<(), String> {
+		let err = |e: String| format!("{}: {}: {}", origin.display(), entry, e);
+		let range = |kind: &str, i: usize, len: usize| {
+			if i < len { Ok(()) } else { Err(err(format!("{} index {} is out of range, the scene declares {} {}s", kind, i, len, kind))) }
+		};
+
+		let object = match desc.target {
+			Target::Sphere(i) => range("sphere", i, buffers.spheres.len()).map(|_| Object::Sphere(i))?,
+			Target::Model(i) => range("model", i, self.models.len()).map(|_| Object::Models(self.models[i].clone()))?,
+			Target::PointLight(i) => range("point light", i, buffers.point_lights.len()).map(|_| Object::PointLight(i))?,
+			Target::DirectionalLight(i) => range("directional light", i, buffers.directional_lights.len()).map(|_| Object::DirectionalLight(i))?,
+			Target::Material(i) => range("material", i, self.declared_materials).map(|_| Object::Material(i))?,
+			Target::Camera => Object::Camera
+		};
+		if let (Target::Model(i), Object::Models(r)) = (&desc.target, &object) {
+			if r.is_empty() {
+				return Err(err(format!("model {} has no mesh to animate", i)));
+			}
+		}
+		let property = desc.property;
+		let n = components(&object, property).ok_or_else(|| err(format!("{:?} has no {:?} property", desc.target, property)))?;
+
+		// Values of the scene file are converted like the scene does, degrees to a quaternion for a rotation and a normalized direction
+		let value = |v: &[f32], what: &str| -> Result<Vec4, String> {
+			if v.len() != n {
+				return Err(err(format!("{} has {} components, {:?} has {}", what, v.len(), property, n)));
+			}
+			let mut value = Vec4::zeros();
+			value.as_mut_slice()[..n].copy_from_slice(v);
+			match property {
+				Property::Rotation => Ok(from_quaternion(Quaternion::from_euler(value.x.to_radians(), value.y.to_radians(), value.z.to_radians()))),
+				Property::Dir if value.norm() == 0.0 => Err(err(format!("{} can't be a zero direction", what))),
+				Property::Dir => Ok(value.normalize()),
+				_ => Ok(value)
+			}
+		};
+		let axis = |a: [f32; 3], driver: &str| {
+			let axis = Vec3::from(a);
+			if axis.norm() == 0.0 { Err(err(format!("the {} axis can't be zero", driver))) } else { Ok(axis.normalize()) }
+		};
+
+		let driver = match &desc.driver {
+			DriverDesc::Keyframes { keys, repeat } => {
+				if keys.is_empty() {
+					return Err(err("keyframes need at least one key".to_string()));
+				}
+				let mut converted: Vec<Key> = Vec::with_capacity(keys.len());
+				for (k, key) in keys.iter().enumerate() {
+					let mut v = value(key.value.components(), &format!("key {}", k))?;
+					if let Some(prev) = converted.last() {
+						if key.time <= prev.time {
+							return Err(err(format!("key {}: time must be greater than the time of the previous key", k)));
+						}
+						if property == Property::Rotation && prev.value.dot(&v) < 0.0 {
+							v = -v; // Same rotation in the hemisphere of the previous key, so slerp takes the shortest arc
+						}
+						if property == Property::Dir && prev.value.dot(&v) <= -1.0 + 1e-6 {
+							return Err(err(format!("key {}: direction is opposite to the previous one, add a key in between", k)));
+						}
+					}
+					converted.push(Key { time: key.time, value: v, easing: key.easing });
+				}
+				Driver::Keyframes { keys: converted, repeat: *repeat }
+			},
+			DriverDesc::Orbit { center, axis: a, speed } => {
+				if property != Property::Pos {
+					return Err(err(format!("Orbit drives positions, not {:?}", property)));
+				}
+				Driver::Orbit { center: Vec3::from(*center), axis: axis(*a, "orbit")?, speed: speed.to_radians() }
+			},
+			DriverDesc::Oscillate { amplitude, period, phase } => {
+				if *period <= 0.0 {
+					return Err(err("the oscillation period must be greater than zero".to_string()));
+				}
+				if property == Property::Dir {
+					return Err(err("Dir can't oscillate, spin it instead".to_string()));
+				}
+				let amplitude = amplitude.components();
+				if amplitude.len() != n {
+					return Err(err(format!("amplitude has {} components, {:?} has {}", amplitude.len(), property, n)));
+				}
+				let mut a = Vec4::zeros();
+				a.as_mut_slice()[..n].copy_from_slice(amplitude);
+				if property == Property::Rotation {
+					a = a.map(f32::to_radians);
+				}
+				Driver::Oscillate { amplitude: a, period: *period, phase: *phase }
+			},
+			DriverDesc::Spin { axis: a, speed } => {
+				if property != Property::Rotation && property != Property::Dir {
+					return Err(err(format!("Spin drives rotations and directions, not {:?}", property)));
+				}
+				Driver::Spin { axis: axis(*a, "spin")?, speed: speed.to_radians() }
+			}
+		};
+
+		let base = get(&object, property, buffers);
+		self.tracks.push(Track { object, property, driver, base });
+		Ok(())
+	}
+
+	/// True when some objects, lights or materials are animated
+	pub fn animates_scene(&self) -> bool {
+		self.tracks.iter().any(|t| !matches!(t.object, Object::Camera))
+	}
+
+	pub fn animates_camera(&self) -> bool {
+		self.tracks.iter().any(|t| matches!(t.object, Object::Camera))
+	}
+
+	/// Buffers holding values written by this animation that `next` doesn't overwrite, they have to be uploaded again from the scene
+	pub fn stale_buffers(&self, next: &Animation) -> StaleBuffers {
+		let mut stale = StaleBuffers::default();
+		let dropped = self.tracks.iter().filter(|t| !next.tracks.iter().any(|n| n.object == t.object && n.property == t.property));
+		for track in dropped {
+			match track.object {
+				Object::Sphere(_) => stale.spheres = true,
+				Object::Models(_) => stale.models = true,
+				Object::PointLight(_) | Object::DirectionalLight(_) => stale.lights = true,
+				Object::Material(_) => stale.materials = true,
+				Object::Camera => ()
+			}
+		}
+		stale
+	}
+
+	/// Writes the values at t seconds into the buffers
+	pub fn apply(&self, t: f32, buffers: &mut SceneBuffers) {
+		for track in self.tracks.iter().filter(|t| !matches!(t.object, Object::Camera)) {
+			set(&track.object, track.property, track.value_at(t, track.base), buffers);
+		}
+	}
+
+	/// Moves the camera at t seconds, starting from the given camera and field of view in radians
+	pub fn apply_camera(&self, t: f32, camera: &mut Camera, fov: &mut f32) {
+		for track in self.tracks.iter().filter(|t| matches!(t.object, Object::Camera)) {
+			match track.property {
+				Property::Pos => {
+					let pos = track.value_at(t, Vec4::from(camera.pos));
+					camera.pos = [pos.x, pos.y, pos.z, 0.0];
+				},
+				Property::Rotation => camera.orientation = track.value_at(t, Vec4::from(camera.orientation)).into(),
+				Property::Fov => *fov = track.value_at(t, scalar(fov.to_degrees())).x.clamp(1e-3, 360.0).to_radians(),
+				_ => ()
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn track(target: Target, property: Property, src: &str) -> TrackDesc {
+		TrackDesc { target, property, driver: ron::from_str(src).unwrap() }
+	}
+
+	// Sphere 0 at the origin and a point light, animated by the tracks, returns the sphere and the light at each time
+	fn sample(tracks: &[TrackDesc], times: &[f32]) -> Vec<(Sphere, PointLight)> {
+		let mut spheres = vec![Sphere::new([0.0, 0.0, 0.0], 1.0, 0)];
+		let mut point_lights = vec![PointLight::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(1.0, 1.0, 1.0), 10.0)];
+		let mut buffers = SceneBuffers { spheres: &mut spheres, models: &mut [], point_lights: &mut point_lights, directional_lights: &mut [], materials: &mut [] };
+
+		let mut animation = Animation::new(Vec::new(), 0);
+		for t in tracks {
+			animation.add(t, &buffers, "animations[0]", Path::new("test.ron")).unwrap();
+		}
+		times.iter().map(|t| {
+			animation.apply(*t, &mut buffers);
+			(buffers.spheres[0], buffers.point_lights[0])
+		}).collect()
+	}
+
+	#[test]
+	fn keyframes_repeat_and_ease() {
+		let keys = "keys: [(time: 1.0, value: 0.0, easing: EaseIn), (time: 3.0, value: 8.0, easing: Step), (time: 4.0, value: 2.0)]";
+		let radius = |repeat: &str, times: &[f32]| -> Vec<f32> {
+			let t = track(Target::Sphere(0), Property::Radius, &format!("Keyframes({}, repeat: {})", keys, repeat));
+			sample(&[t], times).iter().map(|(s, _)| s.r).collect()
+		};
+
+		assert_eq!(radius("Once", &[0.0, 2.0, 3.5, 5.0]), vec![0.0, 1.0, 8.0, 2.0]); // Cubic ease in reaches 1/8 halfway, Step holds the key
+		assert_eq!(radius("Loop", &[5.0, 9.5]), vec![1.0, 8.0]);
+		assert_eq!(radius("PingPong", &[4.0, 5.0, 8.0]), vec![2.0, 8.0, 1.0]);
+	}
+
+	#[test]
+	fn drivers_start_from_the_scene() {
+		let orbit = track(Target::PointLight(0), Property::Pos, "Orbit(center: (0.0, 0.0, 1.0), axis: (0.0, 1.0, 0.0), speed: 90.0)");
+		let oscillate = track(Target::PointLight(0), Property::Intensity, "Oscillate(amplitude: 2.0, period: 4.0)");
+		let frames = sample(&[orbit, oscillate], &[0.0, 1.0, 3.0]);
+
+		let pos = |i: usize| Vec3::new(frames[i].1.pos[0], frames[i].1.pos[1], frames[i].1.pos[2]);
+		assert!((pos(0) - Vec3::new(0.0, 0.0, 5.0)).norm() < 1e-5);
+		assert!((pos(1) - Vec3::new(4.0, 0.0, 1.0)).norm() < 1e-5); // A quarter turn around the center
+		assert_eq!(frames.iter().map(|(_, l)| (l.intensity * 1e3).round() / 1e3).collect::<Vec<_>>(), vec![10.0, 12.0, 8.0]);
+	}
+
+	#[test]
+	fn dropped_tracks_leave_stale_buffers() {
+		let mut spheres = vec![Sphere::new([0.0, 0.0, 0.0], 1.0, 0)];
+		let mut point_lights = vec![PointLight::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(1.0, 1.0, 1.0), 10.0)];
+		let buffers = SceneBuffers { spheres: &mut spheres, models: &mut [], point_lights: &mut point_lights, directional_lights: &mut [], materials: &mut [] };
+		let animation = |tracks: &[TrackDesc]| {
+			let mut animation = Animation::new(Vec::new(), 0);
+			for t in tracks {
+				animation.add(t, &buffers, "animations[0]", Path::new("test.ron")).unwrap();
+			}
+			animation
+		};
+		let radius = || track(Target::Sphere(0), Property::Radius, "Oscillate(amplitude: 0.5, period: 1.0)");
+		let intensity = || track(Target::PointLight(0), Property::Intensity, "Oscillate(amplitude: 2.0, period: 4.0)");
+		let spin = || track(Target::Sphere(0), Property::Rotation, "Spin(axis: (0.0, 1.0, 0.0), speed: 90.0)");
+
+		let old = animation(&[radius(), intensity()]);
+		assert_eq!(old.stale_buffers(&animation(&[radius(), intensity()])), StaleBuffers::default());
+		assert_eq!(old.stale_buffers(&animation(&[intensity(), spin()])), StaleBuffers { spheres: true, ..StaleBuffers::default() }); // The rotation doesn't write the radius
+		assert_eq!(animation(&[]).stale_buffers(&old), StaleBuffers::default());
+	}
+
+	#[test]
+	fn models_without_meshes_are_reported() {
+		let buffers = SceneBuffers { spheres: &mut [], models: &mut [], point_lights: &mut [], directional_lights: &mut [], materials: &mut [] };
+		let mut animation = Animation::new(vec![0..2, 2..2], 0); // The file of the second model has no triangles
+		let spin = track(Target::Model(1), Property::Rotation, "Spin(axis: (0.0, 1.0, 0.0), speed: 90.0)");
+		assert_eq!(animation.add(&spin, &buffers, "animations[3]", Path::new("test.ron")).unwrap_err(), "test.ron: animations[3]: model 1 has no mesh to animate");
+	}
+}
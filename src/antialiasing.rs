@@ -0,0 +1,85 @@
+// Anti-aliasing settings, the sampling itself is done by antialiasing.glsl and its port in cpu.rs
+
+use std::str::FromStr;
+
+/// Distribution of the rays inside a pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+	Stratified, // One ray at a random position in each cell of a grid covering the pixel
+	BlueNoise, // Low discrepancy sequence shifted by interleaved gradient noise, the error looks like fine noise instead of clumps
+}
+
+/// Weight of a ray depending on its distance to the center of the pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+	Box, // Same weight inside the pixel
+	Tent, // Linear falloff, reaches 0 at the center of the neighbouring pixels
+	Gaussian, // Smooth falloff over one and a half pixels, the softest one
+}
+
+impl FromStr for Pattern {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"stratified" => Ok(Pattern::Stratified),
+			"blue-noise" => Ok(Pattern::BlueNoise),
+			_ => Err(format!("unknown pattern {}, expected stratified or blue-noise", s))
+		}
+	}
+}
+
+impl FromStr for Filter {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"box" => Ok(Filter::Box),
+			"tent" => Ok(Filter::Tent),
+			"gaussian" => Ok(Filter::Gaussian),
+			_ => Err(format!("unknown filter {}, expected box, tent or gaussian", s))
+		}
+	}
+}
+
+impl Filter {
+	/// Largest distance in pixels between a ray and the center of its pixel
+	pub fn radius(self) -> f32 {
+		match self {
+			Filter::Box => 0.5,
+			Filter::Tent => 1.0,
+			Filter::Gaussian => 1.5
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AntiAliasing {
+	pub samples: u32, // Rays per pixel and per frame
+	pub pattern: Pattern,
+	pub filter: Filter,
+	pub accumulate: bool, // Average the frames while the view is static, each frame uses other positions in the pixels
+}
+
+impl AntiAliasing {
+	/// A single ray through the corner of each pixel otherwise
+	pub fn jitter(&self) -> bool {
+		self.samples > 1 || self.accumulate
+	}
+
+	/// Defines used by ray3d.glsl
+	pub fn defines(&self) -> Vec<(&'static str, String)> {
+		let mut defines = vec![
+			("AA_SAMPLES", self.samples.to_string()),
+			("AA_PATTERN", (self.pattern as u32).to_string()),
+			("AA_FILTER", (self.filter as u32).to_string())
+		];
+		if self.jitter() {
+			defines.push(("JITTER", String::from("1")));
+		}
+		if self.accumulate {
+			defines.push(("ACCUMULATE", String::from("1")));
+		}
+		defines
+	}
+}
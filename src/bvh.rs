@@ -0,0 +1,299 @@
+// Bounding volume hierarchy over the triangles of a model, built on the CPU with the surface area heuristic (SAH)
+// The nodes of every model are stored in one buffer, each model points to its root node with `Model::bvh_root`
+// Nodes are built in object space, so the ray is moved into the space of the model before the traversal
+
+use nalgebra_glm::Vec3;
+
+use std::fmt;
+
+const BINS: usize = 12; // Number of candidate split planes per axis
+const TRAVERSAL_COST: f32 = 1.0; // Cost of a ray/box test, relative to a ray/triangle test
+const INTERSECTION_COST: f32 = 1.0;
+const MAX_DEPTH: u32 = 31; // The traversal stack in model.glsl holds BVH_STACK_SIZE (32) nodes, one more than the depth
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(C)]
+pub struct BvhNode {
+	pub min: [f32; 3],
+	pub left_first: u32, // Interior nodes: index of the left child, the right child follows it. Leaves: index of the first triangle
+	pub max: [f32; 3],
+	pub count: u32, // Number of triangles in a leaf, 0 for interior nodes
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct BvhStats {
+	pub triangles: u32,
+	pub nodes: u32,
+	pub leaves: u32,
+	pub max_depth: u32,
+	pub sah_cost: f32, // Expected cost of a ray going through the root box, in ray/triangle tests
+}
+
+impl fmt::Display for BvhStats {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} triangles, {} nodes, {} leaves, depth {}, SAH cost {:.2}", self.triangles, self.nodes, self.leaves, self.max_depth, self.sah_cost)
+	}
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Aabb {
+	min: Vec3,
+	max: Vec3
+}
+
+impl Aabb {
+	fn empty() -> Self {
+		Self {
+			min: Vec3::repeat(f32::INFINITY),
+			max: Vec3::repeat(f32::NEG_INFINITY)
+		}
+	}
+
+	fn grow(&mut self, p: Vec3) {
+		self.min = self.min.inf(&p);
+		self.max = self.max.sup(&p);
+	}
+
+	fn merge(&mut self, other: &Aabb) {
+		self.min = self.min.inf(&other.min);
+		self.max = self.max.sup(&other.max);
+	}
+
+	fn area(&self) -> f32 {
+		let e = self.max - self.min;
+		if e.x < 0.0 || e.y < 0.0 || e.z < 0.0 {
+			return 0.0;
+		}
+		2.0 * (e.x * e.y + e.y * e.z + e.z * e.x)
+	}
+}
+
+struct Builder<'a> {
+	indices: &'a mut [[u32; 4]],
+	bounds: Vec<Aabb>, // Bounds and centroids of the triangles, kept in the same order as `indices`
+	centroids: Vec<Vec3>,
+	offset: u32,
+	nodes: &'a mut Vec<BvhNode>,
+	root_area: f32,
+	stats: BvhStats
+}
+
+/// Builds the BVH of the triangles in `indices`, which get reordered so every leaf covers a contiguous range
+/// `offset` is the index of `indices[0]` in the global indices buffer, returns the index of the root node in `nodes`
+pub fn build(vertices: &[[f32; 4]], indices: &mut [[u32; 4]], offset: u32, nodes: &mut Vec<BvhNode>) -> (u32, BvhStats) {
+	let vertex = |i: u32| {
+		let v = vertices[i as usize];
+		Vec3::new(v[0], v[1], v[2])
+	};
+
+	let mut bounds = Vec::with_capacity(indices.len());
+	let mut centroids = Vec::with_capacity(indices.len());
+	for tri in indices.iter() {
+		let mut b = Aabb::empty();
+		for &i in tri[..3].iter() {
+			b.grow(vertex(i));
+		}
+		bounds.push(b);
+		centroids.push((b.min + b.max) * 0.5);
+	}
+
+	let root = nodes.len() as u32;
+	nodes.push(BvhNode { min: [0.0; 3], left_first: offset, max: [0.0; 3], count: 0 });
+
+	let count = indices.len() as u32;
+	let mut builder = Builder {
+		indices,
+		bounds,
+		centroids,
+		offset,
+		nodes,
+		root_area: 0.0,
+		stats: BvhStats {
+			triangles: count,
+			nodes: 1,
+			..BvhStats::default()
+		}
+	};
+
+	if count > 0 { // Models without triangles are skipped by the traversal, the root is never read
+		builder.subdivide(root as usize, 0, count as usize, 0);
+	}
+
+	(root, builder.stats)
+}
+
+impl Builder<'_> {
+	fn subdivide(&mut self, node_index: usize, first: usize, count: usize, depth: u32) {
+		let mut bounds = Aabb::empty();
+		let mut centroid_bounds = Aabb::empty();
+		for i in first..first + count {
+			bounds.merge(&self.bounds[i]);
+			centroid_bounds.grow(self.centroids[i]);
+		}
+
+		self.nodes[node_index].min = bounds.min.into();
+		self.nodes[node_index].max = bounds.max.into();
+
+		let area = bounds.area();
+		if depth == 0 {
+			self.root_area = area;
+		}
+		self.stats.max_depth = self.stats.max_depth.max(depth);
+
+		let leaf_cost = INTERSECTION_COST * count as f32;
+		let split = if count > 1 && depth < MAX_DEPTH && area > 0.0 {
+			self.find_split(first, count, &centroid_bounds, area)
+		} else {
+			None
+		};
+
+		let (axis, plane) = match split {
+			Some((axis, plane, cost)) if cost < leaf_cost => (axis, plane),
+			_ => return self.make_leaf(node_index, first, count, area)
+		};
+
+		// Partition the triangles around the split plane
+		let (min, extent) = (centroid_bounds.min[axis], centroid_bounds.max[axis] - centroid_bounds.min[axis]);
+		let mut i = first;
+		let mut j = first + count;
+		while i < j {
+			if bin_of(self.centroids[i][axis], min, extent) < plane {
+				i += 1;
+			} else {
+				j -= 1;
+				self.indices.swap(i, j);
+				self.bounds.swap(i, j);
+				self.centroids.swap(i, j);
+			}
+		}
+
+		let left_count = i - first;
+		if left_count == 0 || left_count == count {
+			return self.make_leaf(node_index, first, count, area);
+		}
+
+		let left_index = self.nodes.len();
+		let placeholder = BvhNode { min: [0.0; 3], left_first: 0, max: [0.0; 3], count: 0 };
+		self.nodes.push(placeholder);
+		self.nodes.push(placeholder);
+		self.nodes[node_index].left_first = left_index as u32;
+		self.nodes[node_index].count = 0;
+		self.stats.nodes += 2;
+		self.stats.sah_cost += TRAVERSAL_COST * self.relative_area(area);
+
+		self.subdivide(left_index, first, left_count, depth + 1);
+		self.subdivide(left_index + 1, i, count - left_count, depth + 1);
+	}
+
+	// Returns the axis, the first bin of the right side and the SAH cost of the cheapest binned split
+	fn find_split(&self, first: usize, count: usize, centroid_bounds: &Aabb, area: f32) -> Option<(usize, usize, f32)> {
+		let mut best: Option<(usize, usize, f32)> = None;
+
+		for axis in 0..3 {
+			let min = centroid_bounds.min[axis];
+			let extent = centroid_bounds.max[axis] - min;
+			if extent <= 0.0 {
+				continue;
+			}
+
+			let mut bin_bounds = [Aabb::empty(); BINS];
+			let mut bin_counts = [0usize; BINS];
+			for i in first..first + count {
+				let b = bin_of(self.centroids[i][axis], min, extent);
+				bin_bounds[b].merge(&self.bounds[i]);
+				bin_counts[b] += 1;
+			}
+
+			// Sweep from the right to get the area and count of every right side, then from the left to evaluate the planes
+			let mut right_areas = [0.0; BINS];
+			let mut right_counts = [0usize; BINS];
+			let mut right = Aabb::empty();
+			let mut right_count = 0;
+			for b in (1..BINS).rev() {
+				right.merge(&bin_bounds[b]);
+				right_count += bin_counts[b];
+				right_areas[b] = right.area();
+				right_counts[b] = right_count;
+			}
+
+			let mut left = Aabb::empty();
+			let mut left_count = 0;
+			for plane in 1..BINS {
+				left.merge(&bin_bounds[plane - 1]);
+				left_count += bin_counts[plane - 1];
+				if left_count == 0 || right_counts[plane] == 0 {
+					continue;
+				}
+
+				let cost = TRAVERSAL_COST + INTERSECTION_COST * (left.area() * left_count as f32 + right_areas[plane] * right_counts[plane] as f32) / area;
+				if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+					best = Some((axis, plane, cost));
+				}
+			}
+		}
+
+		best
+	}
+
+	fn make_leaf(&mut self, node_index: usize, first: usize, count: usize, area: f32) {
+		self.nodes[node_index].left_first = self.offset + first as u32;
+		self.nodes[node_index].count = count as u32;
+		self.stats.leaves += 1;
+		self.stats.sah_cost += INTERSECTION_COST * count as f32 * self.relative_area(area);
+	}
+
+	fn relative_area(&self, area: f32) -> f32 {
+		if self.root_area > 0.0 { area / self.root_area } else { 1.0 }
+	}
+}
+
+fn bin_of(c: f32, min: f32, extent: f32) -> usize {
+	(((c - min) / extent * BINS as f32) as usize).min(BINS - 1)
+}
+
+/// Slab test, true if the ray enters the box before `max_dist`
+fn hits_box(origin: Vec3, inv_dir: Vec3, node: &BvhNode, max_dist: f32) -> bool {
+	let t0 = (Vec3::from(node.min) - origin).component_mul(&inv_dir);
+	let t1 = (Vec3::from(node.max) - origin).component_mul(&inv_dir);
+	let t_near = t0.inf(&t1);
+	let t_far = t0.sup(&t1);
+
+	let t_enter = t_near.max();
+	let t_exit = t_far.min();
+	t_exit >= t_enter.max(0.0) && t_enter < max_dist
+}
+
+/// CPU version of the traversal loop of model.glsl, the ray must be in the object space of the model
+/// `tri_hit` receives the global index of every triangle of the visited leaves and returns the distance to it on a hit
+/// Nodes further than the closest hit are skipped, with `any_hit` the traversal stops at the first hit
+/// Returns the distance to the closest hit, infinity if nothing was hit
+pub fn traverse<F: FnMut(u32) -> Option<f32>>(nodes: &[BvhNode], root: u32, origin: Vec3, dir: Vec3, any_hit: bool, mut tri_hit: F) -> f32 {
+	let inv_dir = Vec3::new(1.0 / dir.x, 1.0 / dir.y, 1.0 / dir.z);
+	let mut closest = f32::INFINITY;
+
+	let mut stack = Vec::with_capacity(MAX_DEPTH as usize + 1);
+	stack.push(root);
+
+	while let Some(node_index) = stack.pop() {
+		let node = &nodes[node_index as usize];
+		if !hits_box(origin, inv_dir, node, closest) {
+			continue;
+		}
+
+		if node.count > 0 { // Leaf
+			for i in node.left_first..node.left_first + node.count {
+				if let Some(d) = tri_hit(i) {
+					if any_hit {
+						return d;
+					}
+					closest = closest.min(d);
+				}
+			}
+		} else {
+			stack.push(node.left_first);
+			stack.push(node.left_first + 1);
+		}
+	}
+
+	closest
+}
@@ -1,7 +1,9 @@
 use crate::vulkano::pipeline::shader::SpecializationConstants as SpecConstsTrait;
 use crate::vulkano::pipeline::shader::SpecializationMapEntry;
 
-#[derive(Debug, Copy, Clone)]
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 #[allow(non_snake_case)]
 #[repr(C)] // So the in-memory representation of the structure is compatible with the shader
 pub struct Camera {
@@ -26,4 +28,135 @@ unsafe impl SpecConstsTrait for Camera {
 
 		&DESCRIPTORS
     }
-}
\ No newline at end of file
+}
+
+/// Thin lens in front of the camera, the rays of a pixel start anywhere on the lens and meet on the focal plane
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(C)]
+pub struct Lens {
+	pub aperture: f32, // Radius of the lens, 0 for a pinhole camera with everything in focus
+	pub focal_distance: f32, // Distance from the camera to the plane in focus
+	pub blades: u32, // The lens is a polygon with this many sides, a disk below 3, sets the shape of the bokeh
+	pub blade_rotation: f32, // Radians
+	pub autofocus: u32, // 1 to focus on the surface at the center of the screen, the focal distance is used when there is none
+}
+
+/// Mapping from the pixels to the directions of the primary rays
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Projection {
+	Perspective,
+	Orthographic, // Parallel rays, the size of the objects doesn't depend on their distance
+	Fisheye, // Equidistant, the angle from the center of the view grows linearly with the distance to the center of the image
+	Equirectangular, // Full 360 panorama, the longitude spans the width of the image and the latitude its height
+}
+
+impl FromStr for Projection {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"perspective" => Ok(Projection::Perspective),
+			"orthographic" => Ok(Projection::Orthographic),
+			"fisheye" => Ok(Projection::Fisheye),
+			"equirectangular" => Ok(Projection::Equirectangular),
+			_ => Err(format!("unknown projection {}, expected perspective, orthographic, fisheye or equirectangular", s))
+		}
+	}
+}
+
+impl Projection {
+	/// Following projection, to cycle through them at runtime
+	pub fn next(self) -> Self {
+		match self {
+			Projection::Perspective => Projection::Orthographic,
+			Projection::Orthographic => Projection::Fisheye,
+			Projection::Fisheye => Projection::Equirectangular,
+			Projection::Equirectangular => Projection::Perspective
+		}
+	}
+}
+
+/// Projection parameters, the shader generates the primary rays from them
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(C)]
+pub struct View {
+	pub projection: u32, // `Projection` as u32
+	pub fov: f32, // Horizontal field of view in radians, used by the perspective and fisheye projections
+	pub ortho_width: f32, // Width of the view in scene units, used by the orthographic projection
+}
+
+/// Widest perspective view, the image plane is at infinity at 180 degrees
+pub const MAX_PERSPECTIVE_FOV: f32 = 179.0;
+
+impl View {
+	/// Brings the fov in the range of the projection, animations, camera paths and the other projections allow up to 360 degrees
+	pub fn clamp_fov(&mut self) {
+		if self.projection() == Projection::Perspective {
+			self.fov = self.fov.min(MAX_PERSPECTIVE_FOV.to_radians());
+		}
+	}
+
+	pub fn projection(&self) -> Projection {
+		match self.projection {
+			0 => Projection::Perspective,
+			1 => Projection::Orthographic,
+			2 => Projection::Fisheye,
+			_ => Projection::Equirectangular
+		}
+	}
+}
+
+/// Push constants of ray3d.glsl, changes every frame
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct PushConstants {
+	pub camera: Camera,
+	pub lens: Lens,
+	pub view: View,
+	pub sample_index: u32, // Frames accumulated before this one, 0 restarts the accumulation
+}
+
+impl PushConstants {
+	pub fn new(camera: Camera, lens: Lens, view: View, sample_index: u32) -> Self {
+		Self {
+			camera,
+			lens,
+			view,
+			sample_index
+		}
+	}
+}
+
+unsafe impl SpecConstsTrait for PushConstants {
+    fn descriptors() -> &'static [SpecializationMapEntry] {
+        static DESCRIPTORS: [SpecializationMapEntry; 5] = [
+			SpecializationMapEntry {
+				constant_id: 0,
+				offset: 0,
+				size: 16
+			},
+			SpecializationMapEntry {
+				constant_id: 0,
+				offset: 16,
+				size: 16
+			},
+			SpecializationMapEntry {
+				constant_id: 0,
+				offset: 32,
+				size: 20
+			},
+			SpecializationMapEntry {
+				constant_id: 0,
+				offset: 52,
+				size: 12
+			},
+			SpecializationMapEntry {
+				constant_id: 0,
+				offset: 64,
+				size: 4
+			}
+		];
+
+		&DESCRIPTORS
+    }
+}
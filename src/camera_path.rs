@@ -0,0 +1,291 @@
+// Keyframed camera paths, captured from the window and played back so fly-throughs can be recorded again identically
+// Positions and fields of view follow Catmull-Rom splines through the keyframes, orientations are interpolated with slerp or squad
+
+use std::fs;
+use std::ops::{Add, Mul, Sub};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use nalgebra_glm::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+use crate::quaternion::Quaternion;
+
+const KEYFRAME_INTERVAL: f32 = 2.0; // Seconds between a captured keyframe and the previous one, the times can be edited in the file
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Interpolation {
+	Slerp, // Constant angular speed between two keyframes, the speed jumps at the keyframes
+	#[default]
+	Squad, // Smooth through the keyframes
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Keyframe {
+	pub time: f32, // Seconds from the start of the path
+	pub pos: [f32; 3],
+	pub orientation: [f32; 4], // Quaternion
+	pub fov: f32, // Degrees
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CameraPath {
+	#[serde(default)]
+	pub interpolation: Interpolation,
+	#[serde(default)]
+	pub keyframes: Vec<Keyframe>, // Sorted by time
+}
+
+impl CameraPath {
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+		let path = path.as_ref();
+		let src = fs::read_to_string(path)
+			.map_err(|e| format!("Failed to read camera path {}: {}", path.display(), e))?;
+
+		let camera_path: Self = ron::from_str(&src).map_err(|e| format!("{}:{}", path.display(), e))?;
+		for (i, pair) in camera_path.keyframes.windows(2).enumerate() {
+			if pair[1].time <= pair[0].time {
+				return Err(format!("{}: keyframe {}: time must be greater than the time of the previous keyframe", path.display(), i + 1));
+			}
+		}
+		if let Some(i) = camera_path.keyframes.iter().position(|k| k.fov <= 0.0 || k.fov > 360.0) {
+			return Err(format!("{}: keyframe {}: fov must be between 0 and 360 degrees", path.display(), i));
+		}
+		// Normalizing a zero quaternion gives NaN orientations
+		if let Some(i) = camera_path.keyframes.iter().position(|k| { let n = Quaternion::from(k.orientation).norm(); !(n.is_finite() && n > 1e-6) }) {
+			return Err(format!("{}: keyframe {}: orientation can't be a zero quaternion", path.display(), i));
+		}
+		Ok(camera_path)
+	}
+
+	pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+		let path = path.as_ref();
+		let src = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+			.map_err(|e| format!("Failed to serialize camera path: {}", e))?;
+		fs::write(path, src).map_err(|e| format!("Failed to write camera path {}: {}", path.display(), e))
+	}
+
+	pub fn duration(&self) -> f32 {
+		self.keyframes.last().map_or(0.0, |k| k.time)
+	}
+
+	/// Appends a keyframe `KEYFRAME_INTERVAL` seconds after the last one, fov in radians
+	pub fn push(&mut self, camera: Camera, fov: f32) {
+		let time = self.keyframes.last().map_or(0.0, |k| k.time + KEYFRAME_INTERVAL);
+		self.keyframes.push(Keyframe {
+			time,
+			pos: [camera.pos[0], camera.pos[1], camera.pos[2]],
+			orientation: camera.orientation,
+			fov: fov.to_degrees()
+		});
+	}
+
+	/// Camera and field of view in radians at `time` seconds from the start, clamped to the first and last keyframes
+	/// Expects at least one keyframe
+	pub fn sample(&self, time: f32) -> (Camera, f32) {
+		let orientations = self.orientations();
+		if self.keyframes.len() == 1 {
+			let k = &self.keyframes[0];
+			return (Camera { pos: [k.pos[0], k.pos[1], k.pos[2], 0.0], orientation: k.orientation }, k.fov.to_radians());
+		}
+
+		// Segment between keyframes i and i + 1 containing time
+		let i = self.keyframes.iter().rposition(|k| k.time <= time).unwrap_or(0).min(self.keyframes.len() - 2);
+		let h = ((time - self.keyframes[i].time) / (self.keyframes[i + 1].time - self.keyframes[i].time)).clamp(0.0, 1.0);
+
+		let pos = self.catmull_rom(i, h, |k| Vec3::from(k.pos));
+		let fov = self.catmull_rom(i, h, |k| k.fov);
+		let orientation = match self.interpolation {
+			Interpolation::Slerp => orientations[i].slerp(&orientations[i + 1], h),
+			Interpolation::Squad => {
+				let (a, b) = (squad_control(&orientations, i), squad_control(&orientations, i + 1));
+				orientations[i].slerp(&orientations[i + 1], h).slerp(&a.slerp(&b, h), 2.0 * h * (1.0 - h))
+			}
+		};
+
+		(Camera { pos: [pos.x, pos.y, pos.z, 0.0], orientation: orientation.into() }, fov.to_radians())
+	}
+
+	// Orientations of the keyframes, each one in the hemisphere of the previous one so the interpolation takes the shortest arc
+	fn orientations(&self) -> Vec<Quaternion> {
+		let mut orientations: Vec<Quaternion> = Vec::with_capacity(self.keyframes.len());
+		for k in self.keyframes.iter() {
+			let mut q = Quaternion::from(k.orientation).normalize();
+			if orientations.last().is_some_and(|p| p.dot(&q) < 0.0) {
+				q = -q;
+			}
+			orientations.push(q);
+		}
+		orientations
+	}
+
+	// Cubic Hermite curve between keyframes i and i + 1 with the Catmull-Rom tangents, scaled by the times so uneven keyframes keep a smooth speed
+	fn catmull_rom<T, F>(&self, i: usize, h: f32, value: F) -> T where
+		T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+		F: Fn(&Keyframe) -> T {
+		let k = &self.keyframes;
+		let tangent = |j: usize| {
+			let (prev, next) = (j.saturating_sub(1), (j + 1).min(k.len() - 1));
+			(value(&k[next]) - value(&k[prev])) * (1.0 / (k[next].time - k[prev].time))
+		};
+
+		let dt = k[i + 1].time - k[i].time;
+		let (h2, h3) = (h * h, h * h * h);
+		value(&k[i]) * (2.0 * h3 - 3.0 * h2 + 1.0)
+			+ tangent(i) * ((h3 - 2.0 * h2 + h) * dt)
+			+ value(&k[i + 1]) * (-2.0 * h3 + 3.0 * h2)
+			+ tangent(i + 1) * ((h3 - h2) * dt)
+	}
+}
+
+// Inner control point of squad at keyframe i, the orientation itself at the ends of the path
+fn squad_control(orientations: &[Quaternion], i: usize) -> Quaternion {
+	let q = orientations[i];
+	let inv = q.conjugate();
+	let next = (inv * orientations[(i + 1).min(orientations.len() - 1)]).log();
+	let prev = (inv * orientations[i.saturating_sub(1)]).log();
+	q * Quaternion { v: (next.v + prev.v) * -0.25, s: 0.0 }.exp()
+}
+
+/// Captures keyframes into a file and plays the path back
+pub struct PathPlayer {
+	path: CameraPath,
+	file: PathBuf,
+	start: Option<f64>, // t at which the playback started
+	record: bool, // Records the frames while the path plays
+	recording: Arc<AtomicBool>, // Shared with the window
+}
+
+impl PathPlayer {
+	/// Loads the path from `file` when it exists, keyframes are saved to it
+	pub fn new(file: PathBuf, record: bool) -> Result<Self, String> {
+		let path = if file.exists() { CameraPath::load(&file)? } else { CameraPath::default() };
+		Ok(Self {
+			path,
+			file,
+			start: None,
+			record,
+			recording: Arc::new(AtomicBool::new(false))
+		})
+	}
+
+	/// Flag to give to `Canvas::set_recording`
+	pub fn recording(&self) -> Arc<AtomicBool> {
+		self.recording.clone()
+	}
+
+	pub fn add_keyframe(&mut self, camera: Camera, fov: f32) {
+		self.path.push(camera, fov);
+		match self.path.save(&self.file) {
+			Ok(()) => println!("Keyframe {} saved to {}", self.path.keyframes.len() - 1, self.file.display()),
+			Err(e) => eprintln!("{}", e)
+		}
+	}
+
+	/// Starts the playback at t or stops it
+	pub fn toggle(&mut self, t: f64) {
+		if self.start.is_some() {
+			self.stop();
+		} else if self.path.keyframes.len() < 2 {
+			println!("The camera path needs at least 2 keyframes, {} has {}", self.file.display(), self.path.keyframes.len());
+		} else {
+			println!("Playing {} ({}s)", self.file.display(), self.path.duration());
+			self.start = Some(t);
+			if self.record {
+				self.recording.store(true, Ordering::Relaxed);
+			}
+		}
+	}
+
+	fn stop(&mut self) {
+		self.start = None;
+		if self.record {
+			self.recording.store(false, Ordering::Relaxed);
+		}
+	}
+
+	/// Camera and field of view in radians at t while the path plays
+	pub fn update(&mut self, t: f64) -> Option<(Camera, f32)> {
+		let time = (t - self.start?) as f32;
+		if time > self.path.duration() {
+			self.stop();
+			return None;
+		}
+		Some(self.path.sample(time))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+	use std::process;
+
+	fn path(interpolation: Interpolation) -> CameraPath {
+		let keyframe = |time: f32, pos: [f32; 3], yaw: f32, fov: f32| Keyframe { time, pos, orientation: Quaternion::from_euler(0.2, yaw, 0.0).into(), fov };
+		CameraPath {
+			interpolation,
+			keyframes: vec![
+				keyframe(0.0, [0.0, 0.0, 0.0], 0.0, 60.0),
+				keyframe(1.0, [2.0, 1.0, 0.0], 1.0, 90.0),
+				keyframe(3.5, [4.0, 0.0, 3.0], 2.5, 45.0),
+				keyframe(4.0, [0.0, 2.0, 5.0], 4.0, 70.0),
+			]
+		}
+	}
+
+	#[test]
+	fn passes_through_the_keyframes() {
+		for interpolation in [Interpolation::Slerp, Interpolation::Squad] {
+			let path = path(interpolation);
+			for k in path.keyframes.iter() {
+				let (camera, fov) = path.sample(k.time);
+				assert!((Vec3::new(camera.pos[0], camera.pos[1], camera.pos[2]) - Vec3::from(k.pos)).norm() < 1e-4, "{:?} at {}", interpolation, k.time);
+				assert!((fov.to_degrees() - k.fov).abs() < 1e-3);
+				assert!(Quaternion::from(camera.orientation).dot(&Quaternion::from(k.orientation)).abs() > 1.0 - 1e-5);
+			}
+
+			// Halfway through a segment the position stays between the keyframes and the orientation is a unit quaternion
+			let (camera, _) = path.sample(2.25);
+			let q = Quaternion::from(camera.orientation);
+			assert!((q.dot(&q) - 1.0).abs() < 1e-4);
+			assert!(camera.pos[0] > 2.0 && camera.pos[0] < 4.0);
+		}
+	}
+
+	// File removed when dropped, even when an assert fails
+	struct TempFile(PathBuf);
+
+	impl Drop for TempFile {
+		fn drop(&mut self) {
+			let _ = fs::remove_file(&self.0);
+		}
+	}
+
+	#[test]
+	fn load_checks_the_keyframes() {
+		let file = TempFile(env::temp_dir().join(format!("vk_ray3d_camera_path_test_{}.ron", process::id())));
+		let load = |path: &CameraPath| {
+			path.save(&file.0).unwrap();
+			CameraPath::load(&file.0)
+		};
+
+		let valid = load(&path(Interpolation::Slerp)).unwrap();
+		assert_eq!(valid.interpolation, Interpolation::Slerp);
+		assert_eq!(valid.keyframes.len(), 4);
+
+		let mut unsorted = path(Interpolation::Squad);
+		unsorted.keyframes[2].time = 1.0;
+		assert!(load(&unsorted).unwrap_err().ends_with("keyframe 2: time must be greater than the time of the previous keyframe"));
+
+		let mut wide = path(Interpolation::Squad);
+		wide.keyframes[1].fov = 400.0;
+		assert!(load(&wide).unwrap_err().ends_with("keyframe 1: fov must be between 0 and 360 degrees"));
+
+		let mut degenerate = path(Interpolation::Squad);
+		degenerate.keyframes[3].orientation = [0.0; 4];
+		assert!(load(&degenerate).unwrap_err().ends_with("keyframe 3: orientation can't be a zero quaternion"));
+	}
+}
@@ -0,0 +1,180 @@
+// Command line options of the vk_ray3d binary
+
+use structopt::StructOpt;
+use structopt::clap::{Error, ErrorKind};
+
+use std::path::PathBuf;
+
+use crate::antialiasing::{AntiAliasing, Filter, Pattern};
+use crate::camera::{Projection, View};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "vk_ray3d", about = "Raytracing 3D engine written in Rust and GLSL using Vulkan")]
+pub struct Options {
+	/// Scene description file to render
+	#[structopt(parse(from_os_str), default_value = "scenes/default.ron")]
+	pub scene: PathBuf,
+
+	/// Width of the window in pixels
+	#[structopt(long, default_value = "800")]
+	pub width: u32,
+
+	/// Height of the window in pixels
+	#[structopt(long, default_value = "600")]
+	pub height: u32,
+
+	/// Factor applied to the window size to get the size of the rendered image
+	#[structopt(long, default_value = "1.0")]
+	pub scale: f32,
+
+	/// Horizontal field of view in degrees, up to 360 with the fisheye projection
+	#[structopt(long, default_value = "90")]
+	pub fov: f32,
+
+	/// Mapping from the pixels to the rays: perspective, orthographic, fisheye or equirectangular, P cycles through them
+	#[structopt(long, default_value = "perspective")]
+	pub projection: Projection,
+
+	/// Width of the view in scene units with the orthographic projection
+	#[structopt(long, default_value = "10")]
+	pub ortho_width: f32,
+
+	/// Camera path file, K adds the current camera to it and L plays it
+	#[structopt(long, parse(from_os_str), default_value = "camera_path.ron")]
+	pub camera_path: PathBuf,
+
+	/// Play the camera path from the start
+	#[structopt(long)]
+	pub play: bool,
+
+	/// Record the frames to --output-dir while the camera path plays
+	#[structopt(long)]
+	pub record_path: bool,
+
+	/// Target fps, defines how fast animations go and how many frames per second are recorded
+	#[structopt(long, default_value = "30")]
+	pub fps: f64,
+
+	/// Camera speed in scene units per second, Shift moves faster and Alt slower
+	#[structopt(long, default_value = "5")]
+	pub camera_speed: f32,
+
+	/// RON file remapping the keys, for example `(forward: Z, left: Q)`
+	#[structopt(long, parse(from_os_str))]
+	pub key_map: Option<PathBuf>,
+
+	/// Folder containing ray3d.glsl and the files it includes
+	#[structopt(long, parse(from_os_str), default_value = "shader")]
+	pub shader_dir: PathBuf,
+
+	/// Folder in which recordings are saved
+	#[structopt(long, parse(from_os_str), default_value = "Captures")]
+	pub output_dir: PathBuf,
+
+	/// Index of the Vulkan device to use, see --list-devices
+	#[structopt(long)]
+	pub device: Option<usize>,
+
+	/// Print the available Vulkan devices and exit
+	#[structopt(long)]
+	pub list_devices: bool,
+
+	/// Render to png files in --output-dir without opening a window
+	#[structopt(long)]
+	pub headless: bool,
+
+	/// Render a single frame on the CPU to <output-dir>/0.png, does not need a Vulkan device
+	#[structopt(long, conflicts_with = "headless")]
+	pub cpu: bool,
+
+	/// Path trace with diffuse bounces, the frames are averaged while the camera and the scene don't move
+	#[structopt(long)]
+	pub path_tracing: bool,
+
+	/// Frames accumulated by --cpu with --path-tracing or --accumulate
+	#[structopt(long, default_value = "64")]
+	pub samples: u32,
+
+	/// Rays per pixel and per frame, jittered inside the pixel when above 1
+	#[structopt(long, default_value = "1")]
+	pub aa_samples: u32,
+
+	/// Distribution of the jittered rays: stratified or blue-noise
+	#[structopt(long, default_value = "stratified")]
+	pub aa_pattern: Pattern,
+
+	/// Weight of the jittered rays depending on their distance to the center of the pixel: box, tent or gaussian
+	#[structopt(long, default_value = "box")]
+	pub aa_filter: Filter,
+
+	/// Average jittered frames while the camera and the scene don't move, always on with --path-tracing
+	#[structopt(long)]
+	pub accumulate: bool,
+
+	/// Number of frames rendered in headless mode
+	#[structopt(long, default_value = "1")]
+	pub frames: u32,
+
+	/// Animation time between two frames in headless mode, defaults to 1 / fps
+	#[structopt(long)]
+	pub t_step: Option<f64>,
+}
+
+// False for NaN and infinity, which pass a `<= 0.0` check
+fn positive(v: f32) -> bool {
+	v.is_finite() && v > 0.0
+}
+
+impl Options {
+	/// Parses the process arguments, exits with an error message on invalid input
+	pub fn parse() -> Self {
+		let options = Self::from_args();
+
+		let invalid = if options.width == 0 || options.height == 0 {
+			Some("The window size can't be zero")
+		} else if !positive(options.scale) {
+			Some("--scale must be greater than zero")
+		} else if !(options.fov > 0.0 && options.fov <= 360.0) {
+			Some("--fov must be between 0 and 360 degrees")
+		} else if options.projection == Projection::Perspective && options.fov >= 180.0 {
+			Some("--fov must be below 180 degrees with the perspective projection")
+		} else if !positive(options.ortho_width) {
+			Some("--ortho-width must be greater than zero")
+		} else if !positive(options.camera_speed) {
+			Some("--camera-speed must be greater than zero")
+		} else if !(options.fps.is_finite() && options.fps > 0.0) {
+			Some("--fps must be greater than zero")
+		} else if options.samples == 0 {
+			Some("--samples must be greater than zero")
+		} else if options.aa_samples == 0 {
+			Some("--aa-samples must be greater than zero")
+		} else if options.t_step.is_some_and(|t| !(t.is_finite() && t >= 0.0)) {
+			Some("--t-step can't be negative")
+		} else {
+			None
+		};
+
+		if let Some(message) = invalid {
+			Error::with_description(message, ErrorKind::InvalidValue).exit();
+		}
+
+		options
+	}
+
+	pub fn view(&self) -> View {
+		View {
+			projection: self.projection as u32,
+			fov: self.fov.to_radians(),
+			ortho_width: self.ortho_width
+		}
+	}
+
+	pub fn anti_aliasing(&self) -> AntiAliasing {
+		AntiAliasing {
+			samples: self.aa_samples,
+			pattern: self.aa_pattern,
+			filter: self.aa_filter,
+			accumulate: self.accumulate || self.path_tracing // The path tracer needs many frames
+		}
+	}
+}
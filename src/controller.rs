@@ -0,0 +1,217 @@
+// First person camera driven by the keyboard and the mouse
+// Keys are tracked while they are held and the camera moves by its speed times the real frame time, so the motion doesn't depend on the keyboard repeat rate or the fps
+
+use std::collections::HashSet;
+use std::f32::consts::FRAC_PI_2;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use compute_vk::winit::event::{DeviceEvent, ElementState, Event, VirtualKeyCode, WindowEvent};
+use nalgebra_glm::Vec3;
+use serde::Deserialize;
+
+use crate::camera::Camera;
+use crate::quaternion::Quaternion;
+
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01; // Looking straight up or down would flip the view
+const MAX_FRAME_TIME: f32 = 0.1; // Seconds, the camera doesn't jump after a long frame like a resize
+
+/// Keys of the camera, virtual key codes so they follow the keyboard layout
+/// Written like `(forward: Z, left: Q)` in a key map file, the missing keys keep their default
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct KeyMap {
+	pub forward: VirtualKeyCode,
+	pub backward: VirtualKeyCode,
+	pub left: VirtualKeyCode,
+	pub right: VirtualKeyCode,
+	pub up: VirtualKeyCode,
+	pub down: VirtualKeyCode,
+	pub sprint: VirtualKeyCode, // Held to move faster
+	pub slow: VirtualKeyCode, // Held to move slower
+	pub grab: VirtualKeyCode, // Grabs the cursor for mouse look or releases it
+	pub projection: VirtualKeyCode, // Switches to the next projection
+	pub keyframe: VirtualKeyCode, // Adds the current camera to the camera path
+	pub play: VirtualKeyCode, // Plays the camera path or stops it
+}
+
+impl Default for KeyMap {
+	fn default() -> Self {
+		Self {
+			forward: VirtualKeyCode::W,
+			backward: VirtualKeyCode::S,
+			left: VirtualKeyCode::A,
+			right: VirtualKeyCode::D,
+			up: VirtualKeyCode::Space,
+			down: VirtualKeyCode::LControl,
+			sprint: VirtualKeyCode::LShift,
+			slow: VirtualKeyCode::LAlt,
+			grab: VirtualKeyCode::G,
+			projection: VirtualKeyCode::P,
+			keyframe: VirtualKeyCode::K,
+			play: VirtualKeyCode::L
+		}
+	}
+}
+
+impl KeyMap {
+	/// Reads a key map from a RON file
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+		let path = path.as_ref();
+		let src = fs::read_to_string(path)
+			.map_err(|e| format!("Failed to read key map {}: {}", path.display(), e))?;
+
+		ron::from_str(&src).map_err(|e| format!("{}:{}", path.display(), e))
+	}
+}
+
+pub struct CameraController {
+	pub pos: Vec3,
+	pub yaw: f32, // Radians, around the y axis
+	pub pitch: f32, // Radians, around the x axis, clamped so the camera can't look past the vertical
+	pub speed: f32, // Scene units per second
+	pub sprint_factor: f32,
+	pub slow_factor: f32,
+	pub sensitivity: f32, // Radians per unit of mouse motion
+	key_map: KeyMap,
+	pressed: HashSet<VirtualKeyCode>,
+	grabbed: Arc<AtomicBool>, // Shared with the window, the cursor is hidden and the mouse turns the camera while it's true
+	last_frame: Option<Instant>,
+}
+
+impl CameraController {
+	pub fn new(pos: Vec3, yaw: f32, pitch: f32, speed: f32, key_map: KeyMap) -> Self {
+		Self {
+			pos,
+			yaw,
+			pitch: pitch.clamp(-MAX_PITCH, MAX_PITCH),
+			speed,
+			sprint_factor: 4.0,
+			slow_factor: 0.25,
+			sensitivity: 0.001,
+			key_map,
+			pressed: HashSet::new(),
+			grabbed: Arc::new(AtomicBool::new(false)),
+			last_frame: None
+		}
+	}
+
+	pub fn key_map(&self) -> &KeyMap {
+		&self.key_map
+	}
+
+	/// Key pressed by the event, None for the repeated presses of a held key
+	pub fn key_down(&self, ev: &Event<()>) -> Option<VirtualKeyCode> {
+		match ev {
+			Event::WindowEvent { event: WindowEvent::KeyboardInput { input, .. }, .. } if input.state == ElementState::Pressed => {
+				input.virtual_keycode.filter(|k| !self.pressed.contains(k))
+			},
+			_ => None
+		}
+	}
+
+	/// Flag to give to `Canvas::set_cursor_grab`
+	pub fn cursor_grab(&self) -> Arc<AtomicBool> {
+		self.grabbed.clone()
+	}
+
+	pub fn orientation(&self) -> Quaternion {
+		Quaternion::from_euler(self.pitch, self.yaw, 0.0)
+	}
+
+	pub fn camera(&self) -> Camera {
+		Camera {
+			pos: [self.pos.x, self.pos.y, self.pos.z, 0.0],
+			orientation: self.orientation().into()
+		}
+	}
+
+	/// Tracks the keys and turns the camera with the mouse, returns true when the camera turned
+	pub fn handle_event(&mut self, ev: &Event<()>) -> bool {
+		if self.key_down(ev) == Some(self.key_map.grab) {
+			self.grabbed.fetch_xor(true, Ordering::Relaxed);
+		}
+
+		match ev {
+			Event::WindowEvent { event: WindowEvent::KeyboardInput { input, .. }, .. } => {
+				let key = match input.virtual_keycode {
+					Some(k) => k,
+					None => return false
+				};
+				match input.state {
+					ElementState::Pressed => self.pressed.insert(key),
+					ElementState::Released => self.pressed.remove(&key)
+				};
+			},
+			Event::WindowEvent { event: WindowEvent::Focused(false), .. } => { // The key releases go to the other window
+				self.pressed.clear();
+				self.grabbed.store(false, Ordering::Relaxed);
+			},
+			Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. } if self.grabbed.load(Ordering::Relaxed) => {
+				self.yaw += delta.0 as f32 * self.sensitivity;
+				self.pitch = (self.pitch + delta.1 as f32 * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+				return true;
+			},
+			_ => ()
+		}
+		false
+	}
+
+	/// Moves the camera by the time elapsed since the previous frame, call once per frame, returns true when the camera moved
+	pub fn update(&mut self) -> bool {
+		let now = Instant::now();
+		let dt = self.last_frame.map_or(0.0, |t| now.duration_since(t).as_secs_f32().min(MAX_FRAME_TIME));
+		self.last_frame = Some(now);
+
+		let axis = |positive: VirtualKeyCode, negative: VirtualKeyCode| {
+			self.pressed.contains(&positive) as i32 as f32 - self.pressed.contains(&negative) as i32 as f32
+		};
+		let k = &self.key_map;
+		let direction = Vec3::new(axis(k.right, k.left), axis(k.up, k.down), axis(k.forward, k.backward));
+		if direction == Vec3::zeros() || dt == 0.0 {
+			return false;
+		}
+
+		let mut speed = self.speed;
+		if self.pressed.contains(&k.sprint) {
+			speed *= self.sprint_factor;
+		}
+		if self.pressed.contains(&k.slow) {
+			speed *= self.slow_factor;
+		}
+
+		let velocity = self.orientation().transform_point(direction.normalize() * speed); // Moving the camera in its looking direction
+		self.pos += velocity * dt;
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use compute_vk::winit::event::DeviceId;
+	use std::time::Duration;
+
+	#[test]
+	fn pitch_and_frame_time_are_clamped() {
+		let mut controller = CameraController::new(Vec3::zeros(), 0.0, 3.0, 2.0, KeyMap::default());
+		assert_eq!(controller.pitch, MAX_PITCH);
+
+		let motion = |dy: f64| Event::DeviceEvent { device_id: unsafe { DeviceId::dummy() }, event: DeviceEvent::MouseMotion { delta: (0.0, dy) } };
+		assert!(!controller.handle_event(&motion(-1e4))); // The mouse only turns the camera while the cursor is grabbed
+		controller.cursor_grab().store(true, Ordering::Relaxed);
+		assert!(controller.handle_event(&motion(-1e4)));
+		assert_eq!(controller.pitch, -MAX_PITCH);
+		assert!(controller.handle_event(&motion(1e5)));
+		assert_eq!(controller.pitch, MAX_PITCH);
+
+		// A second long frame moves the camera as much as a MAX_FRAME_TIME one
+		controller.pressed.insert(KeyMap::default().forward);
+		controller.last_frame = Instant::now().checked_sub(Duration::from_secs(1));
+		assert!(controller.update());
+		assert!((controller.pos.norm() - controller.speed * MAX_FRAME_TIME).abs() < 1e-5);
+	}
+}
@@ -0,0 +1,942 @@
+// Pure Rust port of ray3d.glsl, renders the same buffers on the CPU
+// Used as a reference for the shader and as a fallback on machines without a Vulkan device
+// Every function mirrors the GLSL function of the same name, keep them in sync
+
+use compute_vk::image::RgbaImage;
+use compute_vk::winit::dpi::PhysicalSize;
+use nalgebra_glm::{Vec2, Vec3, cross, dot};
+use rayon::prelude::*;
+
+use crate::antialiasing::{AntiAliasing, Filter, Pattern};
+use crate::bvh;
+use crate::camera::{Camera, Lens, Projection, View};
+use crate::geom::{sphere::Sphere, model::Model};
+use crate::light::{PointLight, DirectionalLight};
+use crate::quaternion::Quaternion;
+use crate::scene::Scene;
+
+// consts.glsl
+const RAY_COLLISION_PRECISION: f32 = 0.001;
+const REFLECT_DEPTH: usize = 4;
+const RAY_STACK_SIZE: usize = REFLECT_DEPTH + 1;
+const PATH_DEPTH: usize = 8;
+const ROULETTE_BOUNCE: usize = 2;
+const PI: f32 = std::f32::consts::PI;
+
+#[derive(Debug, Copy, Clone)]
+struct CpuRay {
+	origin: Vec3,
+	dir: Vec3
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Hit {
+	Sphere { index: usize },
+	Model { index: usize, tri_index: usize, uv: Vec2 }
+}
+
+// Closest surface hit by a ray
+#[derive(Debug, Copy, Clone)]
+struct Surface {
+	dist: f32,
+	point: Vec3,
+	normal: Vec3,
+	c: Vec3, // Color of the material or its texture at the point
+	lights: Vec3, // Light received directly from the light sources
+	material_index: usize,
+}
+
+// Ray waiting to be traced, rays split in two at transparent surfaces
+#[derive(Debug, Copy, Clone)]
+struct PendingRay {
+	r: CpuRay,
+	weight: Vec3, // Fraction of the light coming back along the ray which reaches the camera
+	depth: usize, // Number of surfaces hit before this ray
+	medium: Option<usize>, // Material the ray travels through, None for the air
+	reflected: bool, // Reflected light fades with the distance to the surface it comes from
+}
+
+pub struct CpuRenderer<'a> {
+	scene: &'a Scene,
+	textures: Vec<RgbaImage>, // Indexed like `Scene::textures`, with the fallback texture for the files failing to load
+}
+
+impl<'a> CpuRenderer<'a> {
+	pub fn new(scene: &'a Scene) -> Self {
+		Self {
+			scene,
+			textures: scene.textures.load_images()
+		}
+	}
+
+	/// Renders the sum of `frames` frames like the accumulation image of the shader, rows are traced in parallel
+	/// Without jitter the rays go through the top left corner of the pixels
+	pub fn render(&self, size: PhysicalSize<u32>, view: &View, camera: &Camera, lens: &Lens, aa: &AntiAliasing, path_tracing: bool, frames: u32) -> RgbaImage {
+		let orientation = Quaternion::from(camera.orientation);
+		let camera_pos = Vec3::new(camera.pos[0], camera.pos[1], camera.pos[2]);
+		let focus = if lens.aperture > 0.0 { self.focal_distance(camera, lens) } else { 0.0 };
+
+		let mut img = RgbaImage::new(size.width, size.height);
+		let row_len = size.width as usize * 4;
+
+		img.par_chunks_mut(row_len).enumerate().for_each(|(y, row)| {
+			for (x, pixel) in row.chunks_mut(4).enumerate() {
+				let ri = y * size.width as usize + x;
+				let mut sum = (Vec3::zeros(), 0.0); // Colors weighted by the filter and sum of the weights
+
+				for sample_index in 0..frames {
+					let mut frame_sum = (Vec3::zeros(), 0.0);
+					for k in 0..aa.samples {
+						let sample_id = sample_index * aa.samples + k;
+						let mut seed = pcg_hash((ri as u32).wrapping_add(pcg_hash(sample_id)));
+
+						let mut offset = Vec2::zeros(); // From the corner of the pixel
+						let mut weight = 1.0;
+						if aa.jitter() {
+							let jitter = (sample_position(aa, [x as i32, y as i32], sample_id, k, &mut seed) - Vec2::repeat(0.5)) * 2.0 * aa.filter.radius(); // From the center of the pixel
+							offset = Vec2::repeat(0.5) + jitter;
+							weight = filter_weight(aa.filter, jitter);
+						}
+						let mut r = match primary_ray(view, [x as u32, y as u32], size, offset) {
+							Some(r) => r,
+							None => { // Outside of the fisheye circle
+								frame_sum.1 += weight;
+								continue;
+							}
+						};
+						if lens.aperture > 0.0 {
+							r = lens_ray(r, view, lens, focus, &mut seed);
+						}
+
+						r.origin = orientation.transform_point(r.origin) + camera_pos;
+						r.dir = orientation.transform_point(r.dir);
+
+						let col = if path_tracing { self.trace_path(r, &mut seed) } else { self.trace(r) };
+						frame_sum.0 += col * weight;
+						frame_sum.1 += weight;
+					}
+					sum.0 += frame_sum.0;
+					sum.1 += frame_sum.1;
+				}
+
+				let col = if sum.1 > 0.0 { sum.0 / sum.1 } else { Vec3::zeros() };
+				pixel[0] = to_unorm(col.x);
+				pixel[1] = to_unorm(col.y);
+				pixel[2] = to_unorm(col.z);
+				pixel[3] = 255;
+			}
+		});
+
+		img
+	}
+
+	// focal_distance in lens.glsl
+	fn focal_distance(&self, camera: &Camera, lens: &Lens) -> f32 {
+		if lens.autofocus == 0 {
+			return lens.focal_distance;
+		}
+
+		let r = CpuRay {
+			origin: vec3(camera.pos),
+			dir: Quaternion::from(camera.orientation).transform_point(Vec3::new(0.0, 0.0, 1.0))
+		};
+		let (closest_sphere_dist, _) = self.ray_trace_to_spheres(&r);
+		let (closest_model_dist, _) = self.ray_trace_to_models(&r);
+
+		let dist = closest_sphere_dist.min(closest_model_dist);
+		if dist.is_infinite() { lens.focal_distance } else { dist }
+	}
+
+	// trace_ray in ray3d.glsl
+	fn trace(&self, r: CpuRay) -> Vec3 {
+		let first = PendingRay { r, weight: Vec3::new(1.0, 1.0, 1.0), depth: 0, medium: None, reflected: false };
+		let mut stack = [first; RAY_STACK_SIZE];
+		let mut stack_len = 1;
+		let mut col = Vec3::zeros();
+
+		while stack_len > 0 {
+			stack_len -= 1;
+			let PendingRay { r, mut weight, depth, medium, reflected } = stack[stack_len];
+
+			let s = match self.ray_trace_to_surface(&r) {
+				Some(s) => s,
+				None => continue // The ray goes to infinity
+			};
+
+			if let Some(m) = medium { // Beer-Lambert absorption inside the medium
+				weight = weight.component_mul(&self.absorption(m, s.dist));
+			}
+			if reflected {
+				weight *= f32::min(1.0, 1.0 / (s.dist * s.dist));
+			}
+
+			let mat = &self.scene.materials[s.material_index];
+			let diffuse_color = s.lights.component_mul(&s.c) * mat.diffuse_factor * mat.opacity;
+			col += weight.component_mul(&(diffuse_color + vec3(mat.emission))); // Add diffused and emitted light
+
+			if depth + 1 >= REFLECT_DEPTH {
+				continue;
+			}
+
+			let normal = s.normal;
+			let entering = dot(&r.dir, &normal) < 0.0;
+			let facing_normal = if entering { normal } else { -normal };
+
+			// Transparent surfaces split the light between reflection and refraction, the opaque part only reflects
+			let transparency = 1.0 - mat.opacity;
+			let mut fresnel = 0.0;
+			if transparency > 0.0 {
+				let n1 = medium.map_or(1.0, |m| self.scene.materials[m].ior);
+				let n2 = if entering { mat.ior } else { 1.0 }; // Rays leaving an object go back to the air
+				let cos_i = -dot(&r.dir, &facing_normal);
+				fresnel = schlick(cos_i, n1, n2);
+
+				if let Some(dir) = refract(r.dir, facing_normal, n1 / n2) {
+					let refracted_weight = weight * transparency * (1.0 - fresnel);
+					if refracted_weight.max() > 0.0 {
+						stack[stack_len] = PendingRay {
+							r: CpuRay { origin: s.point - facing_normal * RAY_COLLISION_PRECISION, dir },
+							weight: refracted_weight,
+							depth: depth + 1,
+							medium: if entering { Some(s.material_index) } else { None },
+							reflected: false
+						};
+						stack_len += 1;
+					}
+				}
+			}
+
+			let reflected_weight = weight.component_mul(&(s.c * mat.reflexivity * mat.opacity + Vec3::repeat(transparency * fresnel)));
+			if reflected_weight.max() > 0.0 {
+				stack[stack_len] = PendingRay {
+					r: CpuRay { origin: s.point + facing_normal * RAY_COLLISION_PRECISION, dir: reflect(r.dir, normal) },
+					weight: reflected_weight,
+					depth: depth + 1,
+					medium,
+					reflected: true
+				};
+				stack_len += 1;
+			}
+		}
+
+		col
+	}
+
+	// Ray_trace_to_Surface in ray3d.glsl
+	fn ray_trace_to_surface(&self, r: &CpuRay) -> Option<Surface> {
+		let (closest_sphere_dist, closest_si) = self.ray_trace_to_spheres(r);
+		let (closest_model_dist, closest_mi) = self.ray_trace_to_models(r);
+
+		let (dist, hit) = match (closest_si, closest_mi) {
+			(None, None) => return None,
+			(Some(si), _) if closest_sphere_dist < closest_model_dist => (closest_sphere_dist, Hit::Sphere { index: si }),
+			(_, Some((mi, tri_index, uv))) => (closest_model_dist, Hit::Model { index: mi, tri_index, uv }),
+			(Some(si), None) => (closest_sphere_dist, Hit::Sphere { index: si })
+		};
+
+		let point = r.origin + r.dir * dist;
+		Some(Surface {
+			dist,
+			point,
+			normal: self.get_normal(hit, point),
+			c: self.get_color(hit, point),
+			lights: self.lights(hit, point),
+			material_index: self.material_index(hit)
+		})
+	}
+
+	// path.glsl
+
+	fn trace_path(&self, mut r: CpuRay, seed: &mut u32) -> Vec3 {
+		let mut radiance = Vec3::zeros();
+		let mut throughput = Vec3::new(1.0, 1.0, 1.0); // Fraction of the light coming back along the current ray which reaches the camera
+		let mut medium: Option<usize> = None;
+
+		for bounce in 0..PATH_DEPTH {
+			let s = match self.ray_trace_to_surface(&r) {
+				Some(s) => s,
+				None => break
+			};
+
+			if let Some(m) = medium { // Beer-Lambert absorption inside the medium
+				throughput = throughput.component_mul(&self.absorption(m, s.dist));
+			}
+
+			let mat = &self.scene.materials[s.material_index];
+			radiance += throughput.component_mul(&(s.lights.component_mul(&s.c) * mat.diffuse_factor * mat.opacity + vec3(mat.emission))); // Direct and emitted light
+
+			let entering = dot(&r.dir, &s.normal) < 0.0;
+			let facing_normal = if entering { s.normal } else { -s.normal };
+
+			let transparency = 1.0 - mat.opacity;
+			let mut fresnel = 0.0;
+			let mut refracted_dir = None;
+			if transparency > 0.0 {
+				let n1 = medium.map_or(1.0, |m| self.scene.materials[m].ior);
+				let n2 = if entering { mat.ior } else { 1.0 };
+				fresnel = schlick(-dot(&r.dir, &facing_normal), n1, n2);
+				refracted_dir = refract(r.dir, facing_normal, n1 / n2);
+			}
+
+			let diffuse_weight = s.c * mat.diffuse_factor * mat.opacity;
+			let reflected_weight = s.c * mat.reflexivity * mat.opacity + Vec3::repeat(transparency * fresnel);
+			let refracted_weight = if refracted_dir.is_some() { Vec3::repeat(transparency * (1.0 - fresnel)) } else { Vec3::zeros() };
+
+			// Each direction is picked with a probability proportional to its weight, the throughput is divided by that probability
+			let p_diffuse = diffuse_weight.max();
+			let p_reflected = reflected_weight.max();
+			let p_refracted = refracted_weight.max();
+			let total = p_diffuse + p_reflected + p_refracted;
+			if total <= 0.0 {
+				break;
+			}
+
+			let u = random(seed) * total;
+			if u < p_diffuse || p_reflected + p_refracted == 0.0 {
+				throughput = throughput.component_mul(&(diffuse_weight * (total / p_diffuse)));
+				r = CpuRay { origin: s.point + facing_normal * RAY_COLLISION_PRECISION, dir: cosine_sample(facing_normal, seed) };
+			} else if u < p_diffuse + p_reflected || p_refracted == 0.0 {
+				throughput = throughput.component_mul(&(reflected_weight * (total / p_reflected)));
+				r = CpuRay { origin: s.point + facing_normal * RAY_COLLISION_PRECISION, dir: reflect(r.dir, s.normal) };
+			} else {
+				throughput = throughput.component_mul(&(refracted_weight * (total / p_refracted)));
+				r = CpuRay { origin: s.point - facing_normal * RAY_COLLISION_PRECISION, dir: refracted_dir.unwrap() };
+				medium = if entering { Some(s.material_index) } else { None };
+			}
+
+			// Russian roulette, paths carrying little light stop early and the surviving ones make up for them
+			if bounce >= ROULETTE_BOUNCE {
+				let survival = throughput.max().min(1.0);
+				if random(seed) >= survival {
+					break;
+				}
+				throughput /= survival;
+			}
+		}
+
+		radiance
+	}
+
+	// sphere.glsl
+
+	fn ray_trace_to_spheres(&self, r: &CpuRay) -> (f32, Option<usize>) {
+		let mut closest_si = None;
+		let mut closest_d = f32::INFINITY;
+
+		for (i, s) in self.scene.spheres.iter().enumerate() {
+			if let Some(dist) = ray_dist_to_sphere(r, s) {
+				if dist < closest_d {
+					closest_d = dist;
+					closest_si = Some(i);
+				}
+			}
+		}
+
+		(closest_d, closest_si)
+	}
+
+	fn sphere_texture_value(&self, s: &Sphere, impact_point: Vec3) -> Vec3 {
+		let mat = &self.scene.materials[s.material_index as usize];
+		if mat.texture_index != -1 {
+			let uv = point_to_geo(impact_point, s);
+			return sample(&self.textures[mat.texture_index as usize], uv);
+		}
+		vec3(mat.col)
+	}
+
+	// model.glsl
+
+	// Ray_traverse_Model, the ray is moved into the object space of the model with Ray_to_object_space
+	fn ray_traverse_model(&self, r: &CpuRay, model: &Model, any_hit: bool) -> Option<(f32, usize, Vec2)> {
+		if model.indices_start == model.indices_end {
+			return None;
+		}
+
+		let local_r = ray_to_object_space(r, model);
+
+		let mut closest: Option<(f32, usize, Vec2)> = None;
+		bvh::traverse(&self.scene.bvh_nodes, model.bvh_root, local_r.origin, local_r.dir, any_hit, |i| {
+			let indexed_tri = self.scene.indices[i as usize];
+			let a = self.vertex(indexed_tri[0]);
+			let b = self.vertex(indexed_tri[1]);
+			let c = self.vertex(indexed_tri[2]);
+
+			let (d, uv) = ray_dist_to_triangle(&local_r, a, b, c)?;
+			if closest.is_none_or(|(closest_d, _, _)| d < closest_d) {
+				closest = Some((d, i as usize, uv));
+			}
+			Some(d)
+		});
+
+		closest
+	}
+
+	fn ray_dist_to_model(&self, r: &CpuRay, model: &Model) -> Option<(f32, usize, Vec2)> {
+		self.ray_traverse_model(r, model, false)
+	}
+
+	fn ray_hits_models(&self, r: &CpuRay) -> bool {
+		self.scene.models.iter().any(|m| self.ray_traverse_model(r, m, true).is_some())
+	}
+
+	fn ray_trace_to_models(&self, r: &CpuRay) -> (f32, Option<(usize, usize, Vec2)>) {
+		let mut closest_mi = None;
+		let mut closest_model_dist = f32::INFINITY;
+
+		for (i, m) in self.scene.models.iter().enumerate() {
+			if let Some((d, tri_index, uv)) = self.ray_dist_to_model(r, m) {
+				if d < closest_model_dist {
+					closest_model_dist = d;
+					closest_mi = Some((i, tri_index, uv));
+				}
+			}
+		}
+
+		(closest_model_dist, closest_mi)
+	}
+
+	fn model_color(&self, m: &Model, tri_index: usize, uv: Vec2) -> Vec3 {
+		let mat = &self.scene.materials[m.material_index as usize];
+		if mat.texture_index == -1 {
+			return vec3(mat.col);
+		}
+
+		let indexed_tri = self.scene.indices[tri_index];
+		let tex_a = self.uv(indexed_tri[0]);
+		let tex_b = self.uv(indexed_tri[1]);
+		let tex_c = self.uv(indexed_tri[2]);
+
+		let tex_uv = tex_a + uv.x * (tex_b - tex_a) + uv.y * (tex_c - tex_a);
+		sample(&self.textures[mat.texture_index as usize], tex_uv)
+	}
+
+	fn model_normal(&self, m: &Model, tri_index: usize, uv: Vec2) -> Vec3 {
+		let indexed_tri = self.scene.indices[tri_index];
+		let object_normal = if m.smooth_shading != 0 {
+			let n_a = vec3(self.scene.normals[indexed_tri[0] as usize]);
+			let n_b = vec3(self.scene.normals[indexed_tri[1] as usize]);
+			let n_c = vec3(self.scene.normals[indexed_tri[2] as usize]);
+			n_a + (n_b - n_a) * uv.x + (n_c - n_a) * uv.y
+		} else {
+			let a = self.vertex(indexed_tri[0]);
+			let ab = self.vertex(indexed_tri[1]) - a;
+			let ac = self.vertex(indexed_tri[2]) - a;
+			cross(&ab, &ac)
+		};
+
+		// Inverse transpose of rotation * scale
+		Quaternion::from(m.rotation).transform_point(object_normal.component_div(&vec3(m.scale))).normalize()
+	}
+
+	// light.glsl
+
+	fn point_lights_to_sphere(&self, mut impact_point: Vec3, closest_s: &Sphere) -> Vec3 {
+		let mut final_color = Vec3::zeros();
+		let normal = (impact_point - Vec3::new(closest_s.pos[0], closest_s.pos[1], closest_s.pos[2])).normalize();
+		impact_point += normal * RAY_COLLISION_PRECISION;
+
+		for light in self.scene.point_lights.iter() {
+			let to_light = light_pos(light) - impact_point;
+			let ray_to_light = CpuRay { origin: impact_point, dir: to_light };
+
+			// Like the shader, the sphere at index 0 never casts shadows from point lights
+			let (_, closest_si) = self.ray_trace_to_spheres(&ray_to_light);
+			if closest_si.is_none() || closest_si == Some(0) {
+				let to_light_sq_dist = dot(&to_light, &to_light);
+				let diffusion_factor = (dot(&normal, &to_light) / to_light.norm()).clamp(0.0, 1.0);
+				let distance_factor = 1.0 / to_light_sq_dist;
+				final_color += Vec3::from(light.col) * light.intensity * distance_factor * diffusion_factor;
+			}
+		}
+
+		for light in self.scene.directional_lights.iter() {
+			let to_light = -light_dir(light);
+			let ray_to_light = CpuRay { origin: impact_point, dir: to_light };
+
+			let (_, closest_si) = self.ray_trace_to_spheres(&ray_to_light);
+			if closest_si.is_none() {
+				let diffusion_factor = (dot(&normal, &to_light) / to_light.norm()).clamp(0.0, 1.0);
+				final_color += Vec3::from(light.col) * diffusion_factor;
+			}
+		}
+
+		final_color
+	}
+
+	fn point_lights_to_model(&self, mut impact_point: Vec3, closest_m: &Model, tri_index: usize, uv: Vec2) -> Vec3 {
+		let mut final_color = Vec3::zeros();
+		let normal = self.model_normal(closest_m, tri_index, uv);
+		impact_point += normal * RAY_COLLISION_PRECISION; // Shift the impact point a bit outward to limit the dotty effect
+
+		for light in self.scene.point_lights.iter() {
+			let to_light = light_pos(light) - impact_point;
+			let ray_to_light = CpuRay { origin: impact_point, dir: to_light };
+
+			if !self.ray_hits_models(&ray_to_light) {
+				let to_light_sq_dist = dot(&to_light, &to_light);
+				let diffusion_factor = (dot(&normal, &to_light) / to_light.norm()).clamp(0.0, 1.0);
+				let distance_factor = 1.0 / to_light_sq_dist;
+				final_color += Vec3::from(light.col) * light.intensity * distance_factor * diffusion_factor;
+			}
+		}
+
+		for light in self.scene.directional_lights.iter() {
+			let to_light = -light_dir(light);
+			let ray_to_light = CpuRay { origin: impact_point, dir: to_light };
+
+			if !self.ray_hits_models(&ray_to_light) {
+				let diffusion_factor = (dot(&normal, &to_light) / to_light.norm()).clamp(0.0, 1.0);
+				final_color += Vec3::from(light.col) * light.intensity * diffusion_factor;
+			}
+		}
+
+		final_color
+	}
+
+	// Dispatch between the sphere and model versions of the functions above
+
+	fn get_normal(&self, hit: Hit, impact_point: Vec3) -> Vec3 {
+		match hit {
+			Hit::Sphere { index } => {
+				let s = &self.scene.spheres[index];
+				(impact_point - Vec3::new(s.pos[0], s.pos[1], s.pos[2])).normalize()
+			},
+			Hit::Model { index, tri_index, uv } => self.model_normal(&self.scene.models[index], tri_index, uv)
+		}
+	}
+
+	fn get_color(&self, hit: Hit, impact_point: Vec3) -> Vec3 {
+		match hit {
+			Hit::Sphere { index } => self.sphere_texture_value(&self.scene.spheres[index], impact_point),
+			Hit::Model { index, tri_index, uv } => self.model_color(&self.scene.models[index], tri_index, uv)
+		}
+	}
+
+	fn lights(&self, hit: Hit, impact_point: Vec3) -> Vec3 {
+		match hit {
+			Hit::Sphere { index } => self.point_lights_to_sphere(impact_point, &self.scene.spheres[index]),
+			Hit::Model { index, tri_index, uv } => self.point_lights_to_model(impact_point, &self.scene.models[index], tri_index, uv)
+		}
+	}
+
+	fn material_index(&self, hit: Hit) -> usize {
+		let index = match hit {
+			Hit::Sphere { index } => self.scene.spheres[index].material_index,
+			Hit::Model { index, .. } => self.scene.models[index].material_index
+		};
+		index as usize
+	}
+
+	// Fraction of the light left after travelling dist inside the material
+	fn absorption(&self, material_index: usize, dist: f32) -> Vec3 {
+		vec3(self.scene.materials[material_index].absorption).map(|a| (-a * dist).exp())
+	}
+
+	fn vertex(&self, i: u32) -> Vec3 {
+		let v = self.scene.vertices[i as usize];
+		Vec3::new(v[0], v[1], v[2])
+	}
+
+	fn uv(&self, i: u32) -> Vec2 {
+		match self.scene.uvs.get(i as usize) { // Models without texture coordinates don't fill the uvs
+			Some(uv) => Vec2::new(uv[0], uv[1]),
+			None => Vec2::zeros()
+		}
+	}
+}
+
+fn ray_dist_to_sphere(r: &CpuRay, s: &Sphere) -> Option<f32> {
+	let (o, d) = (r.origin, r.dir);
+	let p = Vec3::new(s.pos[0], s.pos[1], s.pos[2]);
+
+	let a = dot(&d, &d);
+	let b = 2.0 * (dot(&d, &o) - dot(&d, &p));
+	let c = dot(&p, &p) - 2.0 * dot(&o, &p) + dot(&o, &o) - s.r * s.r;
+	let delta = b * b - 4.0 * a * c;
+
+	if delta >= 0.0 {
+		let sq_delta = delta.sqrt();
+
+		let t1 = (-b - sq_delta) / (2.0 * a);
+		let t2 = (-b + sq_delta) / (2.0 * a);
+
+		if t1 > RAY_COLLISION_PRECISION { // Do not detect objects behind the ray
+			return Some(t1);
+		} else if t2 > RAY_COLLISION_PRECISION {
+			return Some(t2);
+		}
+	}
+
+	None
+}
+
+// Cramer's rule, see the comment of Ray_dist_to_Triangle in model.glsl
+fn ray_dist_to_triangle(r: &CpuRay, a: Vec3, b: Vec3, c: Vec3) -> Option<(f32, Vec2)> {
+	let d = r.dir;
+	let ab = b - a;
+	let ac = c - a;
+
+	let ac_cross_neg_d = cross(&ac, &-d);
+	let detm = dot(&ab, &ac_cross_neg_d);
+
+	if detm == 0.0 { // Ray is parallel to the triangle
+		return None;
+	}
+
+	let p = r.origin - a;
+	let u = dot(&p, &ac_cross_neg_d) / detm;
+
+	if (0.0..=1.0).contains(&u) {
+		let p_cross_ab = cross(&p, &ab);
+		let v = dot(&d, &p_cross_ab) / detm;
+
+		if (0.0..=1.0).contains(&v) && (u + v) <= 1.0 {
+			let t = dot(&p_cross_ab, &ac) / detm;
+			if t > RAY_COLLISION_PRECISION {
+				return Some((t, Vec2::new(u, v)));
+			}
+		}
+	}
+
+	None
+}
+
+// The direction is not normalized, so a distance along the ray is the same in both spaces
+fn ray_to_object_space(r: &CpuRay, model: &Model) -> CpuRay {
+	let inv_rotation = Quaternion::from(model.rotation).conjugate();
+	let scale = vec3(model.scale);
+	CpuRay {
+		origin: inv_rotation.transform_point(r.origin - vec3(model.pos)).component_div(&scale),
+		dir: inv_rotation.transform_point(r.dir).component_div(&scale)
+	}
+}
+
+fn point_to_geo(point: Vec3, s: &Sphere) -> Vec2 {
+	let sphere_normal = Quaternion::from(s.orientation).conjugate().transform_point((point - vec3(s.pos)).normalize()); // Texture space of the sphere
+
+	let north_vector = Vec3::new(0.0, 1.0, 0.0);
+	let east_vector = Vec3::new(1.0, 0.0, 0.0);
+
+	let lat = dot(&north_vector, &sphere_normal).acos();
+	let v = lat / PI;
+
+	let lon = (dot(&sphere_normal, &east_vector) / lat.sin()).acos() / (2.0 * PI);
+	let u = if dot(&cross(&north_vector, &east_vector), &sphere_normal) > 0.0 {
+		lon
+	} else {
+		1.0 - lon
+	};
+
+	Vec2::new(-u, v)
+}
+
+fn vec3(v: [f32; 4]) -> Vec3 {
+	Vec3::new(v[0], v[1], v[2])
+}
+
+// Unlike f32::fract, always positive like GLSL's fract
+fn fract(x: f32) -> f32 {
+	x - x.floor()
+}
+
+fn reflect(i: Vec3, n: Vec3) -> Vec3 {
+	i - 2.0 * dot(&n, &i) * n
+}
+
+// Same as GLSL's refract but returns None on total internal reflection
+fn refract(i: Vec3, n: Vec3, eta: f32) -> Option<Vec3> {
+	let cos_i = dot(&n, &i);
+	let k = 1.0 - eta * eta * (1.0 - cos_i * cos_i);
+	if k < 0.0 {
+		return None;
+	}
+	Some(eta * i - (eta * cos_i + k.sqrt()) * n)
+}
+
+// Schlick's approximation of the fraction of light reflected when going from n1 to n2, 1 on total internal reflection
+fn schlick(cos_i: f32, n1: f32, n2: f32) -> f32 {
+	let r0 = ((n1 - n2) / (n1 + n2)).powi(2);
+	let mut cos = cos_i;
+	if n1 > n2 { // The transmitted angle is the largest one, use it instead
+		let sin2_t = (n1 / n2).powi(2) * (1.0 - cos_i * cos_i);
+		if sin2_t > 1.0 {
+			return 1.0;
+		}
+		cos = (1.0 - sin2_t).sqrt();
+	}
+	r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+}
+
+// random.glsl
+
+// PCG hash, good enough randomness from the pixel and sample indices
+fn pcg_hash(v: u32) -> u32 {
+	let state = v.wrapping_mul(747796405).wrapping_add(2891336453);
+	let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+	(word >> 22) ^ word
+}
+
+// Uniform in [0, 1), 24 bits so the conversion to float is exact
+fn random(seed: &mut u32) -> f32 {
+	*seed = pcg_hash(*seed);
+	(*seed >> 8) as f32 / 16777216.0
+}
+
+// antialiasing.glsl
+
+// Interleaved gradient noise, neighbouring pixels get very different values
+fn ign(p: Vec2) -> f32 {
+	fract(52.982918 * fract(dot(&p, &Vec2::new(0.06711056, 0.00583715))))
+}
+
+// Position in [0, 1) of the k-th ray of the frame in the pixel, sample_id counts the rays since the accumulation started
+fn sample_position(aa: &AntiAliasing, pixel: [i32; 2], sample_id: u32, k: u32, seed: &mut u32) -> Vec2 {
+	match aa.pattern {
+		Pattern::Stratified => {
+			let mut m = 1; // Cells per side of the grid
+			while m * m < aa.samples {
+				m += 1;
+			}
+			let cell = k * m * m / aa.samples; // Spreads the rays over the grid when the sample count isn't a square
+			let x = (cell % m) as f32 + random(seed);
+			let y = (cell / m) as f32 + random(seed);
+			Vec2::new(x, y) / m as f32
+		},
+		Pattern::BlueNoise => {
+			let p = Vec2::new(pixel[0] as f32, pixel[1] as f32);
+			let shift = Vec2::new(ign(p), ign(p + Vec2::new(47.0, 17.0)));
+			(shift + sample_id as f32 * Vec2::new(0.7548777, 0.5698403)).map(fract) // R2 sequence
+		}
+	}
+}
+
+fn filter_weight(filter: Filter, offset: Vec2) -> f32 {
+	match filter {
+		Filter::Box => 1.0,
+		Filter::Tent => (1.0 - offset.x.abs()).max(0.0) * (1.0 - offset.y.abs()).max(0.0),
+		Filter::Gaussian => (-dot(&offset, &offset) / (2.0 * 0.5 * 0.5)).exp() // Standard deviation of half a pixel
+	}
+}
+
+
+// path.glsl
+
+// Direction in the hemisphere around n with a density proportional to the cosine, which cancels the cosine of diffuse surfaces
+fn cosine_sample(n: Vec3, seed: &mut u32) -> Vec3 {
+	let phi = 2.0 * PI * random(seed);
+	let r2 = random(seed);
+	let r = r2.sqrt();
+
+	let t = cross(&(if n.x.abs() > 0.5 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) }), &n).normalize();
+	let b = cross(&n, &t);
+	t * (phi.cos() * r) + b * (phi.sin() * r) + n * (1.0 - r2).sqrt()
+}
+
+fn light_pos(light: &PointLight) -> Vec3 {
+	Vec3::new(light.pos[0], light.pos[1], light.pos[2])
+}
+
+fn light_dir(light: &DirectionalLight) -> Vec3 {
+	Vec3::new(light.direction[0], light.direction[1], light.direction[2])
+}
+
+// Bilinear sampling with repeat wrapping, like `Sampler::simple_repeat_linear`
+fn sample(texture: &RgbaImage, uv: Vec2) -> Vec3 {
+	let (w, h) = texture.dimensions();
+	let x = uv.x * w as f32 - 0.5;
+	let y = uv.y * h as f32 - 0.5;
+	let (x0, y0) = (x.floor(), y.floor());
+	let (fx, fy) = (x - x0, y - y0);
+
+	let texel = |tx: f32, ty: f32| -> Vec3 {
+		let px = texture.get_pixel((tx as i64).rem_euclid(w as i64) as u32, (ty as i64).rem_euclid(h as i64) as u32);
+		Vec3::new(px[0] as f32, px[1] as f32, px[2] as f32) / 255.0
+	};
+
+	let top = texel(x0, y0) * (1.0 - fx) + texel(x0 + 1.0, y0) * fx;
+	let bottom = texel(x0, y0 + 1.0) * (1.0 - fx) + texel(x0 + 1.0, y0 + 1.0) * fx;
+	top * (1.0 - fy) + bottom * fy
+}
+
+fn to_unorm(c: f32) -> u8 {
+	(c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+// camera.glsl
+
+// Distance from the camera to the image plane in pixels, the field of view spans the width of the image
+fn pixel_depth(size: PhysicalSize<u32>, fov: f32) -> f32 {
+	let half_fov = fov / 2.0;
+	(half_fov.cos() * size.width as f32) / (2.0 * half_fov.sin())
+}
+
+// Camera space ray through a point of the image, offset is in pixels from the top left corner of the pixel
+// None outside of the circle of a fisheye wider than 360 degrees
+fn primary_ray(view: &View, pixel: [u32; 2], size: PhysicalSize<u32>, offset: Vec2) -> Option<CpuRay> {
+	let x = pixel[0] as f32 - size.width as f32 / 2.0 + offset.x; // From the center of the image, y up
+	let y = -(pixel[1] as f32 - size.height as f32 / 2.0) - offset.y;
+
+	match view.projection() {
+		Projection::Perspective => Some(CpuRay { origin: Vec3::zeros(), dir: Vec3::new(x, y, pixel_depth(size, view.fov)).normalize() }),
+		Projection::Orthographic => {
+			let scale = view.ortho_width / size.width as f32;
+			Some(CpuRay { origin: Vec3::new(x * scale, y * scale, 0.0), dir: Vec3::new(0.0, 0.0, 1.0) })
+		},
+		Projection::Fisheye => {
+			let r = (x * x + y * y).sqrt();
+			let theta = r / (size.width as f32 / 2.0) * (view.fov / 2.0); // Angle from the center of the view
+			if theta > PI {
+				return None;
+			}
+			let side = if r > 0.0 { Vec2::new(x, y) / r * theta.sin() } else { Vec2::zeros() };
+			Some(CpuRay { origin: Vec3::zeros(), dir: Vec3::new(side.x, side.y, theta.cos()) })
+		},
+		Projection::Equirectangular => {
+			let longitude = (x / size.width as f32) * 2.0 * PI;
+			let latitude = (y / size.height as f32) * PI;
+			Some(CpuRay { origin: Vec3::zeros(), dir: Vec3::new(latitude.cos() * longitude.sin(), latitude.sin(), latitude.cos() * longitude.cos()) })
+		}
+	}
+}
+
+// lens.glsl
+
+// Uniform point on the lens, in the xy plane of the camera
+fn lens_sample(lens: &Lens, seed: &mut u32) -> Vec2 {
+	let mut u = random(seed);
+	let mut v = random(seed);
+	if lens.blades < 3 { // Disk
+		let theta = 2.0 * PI * v;
+		return u.sqrt() * lens.aperture * Vec2::new(theta.cos(), theta.sin());
+	}
+
+	// Uniform point in one of the triangles between the center and the sides of the polygon
+	let blade_angle = 2.0 * PI / lens.blades as f32;
+	let blade = (random(seed) * lens.blades as f32).floor();
+	if u + v > 1.0 { // Folds the square onto the triangle
+		u = 1.0 - u;
+		v = 1.0 - v;
+	}
+	let a = lens.blade_rotation + blade * blade_angle;
+	let corner_a = Vec2::new(a.cos(), a.sin());
+	let corner_b = Vec2::new((a + blade_angle).cos(), (a + blade_angle).sin());
+	(u * corner_a + v * corner_b) * lens.aperture
+}
+
+// Moves the origin of a camera space ray to a random point of the lens, the ray still goes through the same point of the focal plane
+// The focal surface is a plane for the flat projections and a sphere around the camera for the others
+fn lens_ray(r: CpuRay, view: &View, lens: &Lens, focus: f32, seed: &mut u32) -> CpuRay {
+	let dist = match view.projection() {
+		Projection::Perspective | Projection::Orthographic => focus / r.dir.z,
+		_ => focus
+	};
+	let focus_point = r.origin + r.dir * dist;
+	let sample = lens_sample(lens, seed);
+	let lens_point = r.origin + Vec3::new(sample.x, sample.y, 0.0);
+	CpuRay { origin: lens_point, dir: (focus_point - lens_point).normalize() }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::path::Path;
+
+	// Small xorshift generator, keeps the test deterministic without another dependency
+	struct Rng(u32);
+
+	impl Rng {
+		fn next(&mut self) -> f32 {
+			self.0 ^= self.0 << 13;
+			self.0 ^= self.0 >> 17;
+			self.0 ^= self.0 << 5;
+			self.0 as f32 / u32::MAX as f32
+		}
+
+		fn vec(&mut self, scale: f32) -> Vec3 {
+			Vec3::new(self.next() - 0.5, self.next() - 0.5, self.next() - 0.5) * scale
+		}
+	}
+
+	#[test]
+	fn bvh_traversal_matches_brute_force() {
+		let mut rng = Rng(0x1234_5678);
+		let mut vertices = Vec::new();
+		let mut indices = Vec::new();
+
+		for _ in 0..500 {
+			let center = rng.vec(20.0);
+			let first = vertices.len() as u32;
+			for _ in 0..3 {
+				let v = center + rng.vec(2.0);
+				vertices.push([v.x, v.y, v.z, 0.0]);
+			}
+			indices.push([first, first + 1, first + 2, 0]);
+		}
+
+		let mut nodes = Vec::new();
+		let (root, stats) = bvh::build(&vertices, &mut indices, 0, &mut nodes);
+		assert_eq!(stats.nodes as usize, nodes.len());
+		assert!(stats.max_depth < 32);
+
+		let vertex = |i: u32| Vec3::new(vertices[i as usize][0], vertices[i as usize][1], vertices[i as usize][2]);
+		let tri_dist = |r: &CpuRay, i: u32| {
+			let tri = indices[i as usize];
+			ray_dist_to_triangle(r, vertex(tri[0]), vertex(tri[1]), vertex(tri[2])).map(|(d, _)| d)
+		};
+
+		for _ in 0..2000 {
+			let r = CpuRay { origin: rng.vec(40.0), dir: rng.vec(1.0) };
+
+			let brute = (0..indices.len() as u32)
+				.filter_map(|i| tri_dist(&r, i))
+				.fold(f32::INFINITY, f32::min);
+			let closest = bvh::traverse(&nodes, root, r.origin, r.dir, false, |i| tri_dist(&r, i));
+			let any = bvh::traverse(&nodes, root, r.origin, r.dir, true, |i| tri_dist(&r, i));
+
+			assert_eq!(brute, closest);
+			assert_eq!(brute.is_finite(), any.is_finite());
+		}
+	}
+
+	// Orthographic view of a wall lit by a point light, with a small quad casting a shadow on it and a red sphere in front of it
+	// Each pixel is 0.2 units wide, pixel (px, py) looks along z at x = (px - 20) * 0.2, y = (20 - py) * 0.2
+	fn render_test_scene(sphere_reflexivity: f32) -> RgbaImage {
+		let src = format!("(
+			materials: [(col: (1.0, 1.0, 1.0, 1.0), reflexivity: 0.0, diffuse_factor: 1.0, emission: (0.2, 0.2, 0.2))],
+			spheres: [(pos: (-1.5, 1.5, 9.0), r: 0.8, col: Some((1.0, 0.0, 0.0, 1.0)), reflexivity: Some({}), diffuse_factor: Some(1.0))],
+			models: [
+				(path: \"OBJ/quad.obj\", pos: (0.0, 0.0, 10.0), scale: (3.0, 3.0, 1.0), material: Some(0)),
+				(path: \"OBJ/quad.obj\", pos: (1.0, 1.0, 6.0), scale: (0.3, 0.3, 1.0), material: Some(0)),
+			],
+			point_lights: [(pos: (0.0, 0.0, 2.0), intensity: 40.0)],
+		)", sphere_reflexivity);
+		let scene = Scene::from_desc(ron::from_str(&src).unwrap(), Path::new(""), Path::new("test.ron")).unwrap();
+
+		let view = View { projection: Projection::Orthographic as u32, fov: 1.0, ortho_width: 8.0 };
+		let aa = AntiAliasing { samples: 1, pattern: Pattern::Stratified, filter: Filter::Box, accumulate: false };
+		CpuRenderer::new(&scene).render(PhysicalSize::new(40, 40), &view, &scene.camera.to_camera(), &scene.camera.to_lens(), &aa, false, 1)
+	}
+
+	#[test]
+	fn render_shades_shadows_and_reflects() {
+		let img = render_test_scene(0.5);
+		let px = |x: u32, y: u32| img.get_pixel(x, y).0;
+
+		assert_eq!(px(0, 0), [0, 0, 0, 255]); // Misses everything
+		// Wall at (-2, -2): emission and the light at a distance of sqrt(72), 40 * (8 / sqrt(72)) / 72 + 0.2
+		let lit = to_unorm(40.0 * (8.0 / 72f32.sqrt()) / 72.0 + 0.2);
+		assert!(px(10, 30)[..3].iter().all(|&c| c.abs_diff(lit) <= 1), "{:?}", px(10, 30));
+		// Wall at (2, 2): the small quad is halfway between it and the light, only the emission is left
+		assert_eq!(px(30, 10), [51, 51, 51, 255]);
+		assert_eq!(px(30, 10), px(29, 11));
+
+		// The sphere is red, its reflection of the white wall is tinted by it
+		let plain = render_test_scene(0.0);
+		let center = (12, 12); // Facing the camera, the reflection goes back towards it and hits nothing
+		let edge = (16, 12); // About 60 degrees from the view direction, the reflection reaches the wall
+		assert!(px(center.0, center.1)[0] > 0);
+		assert_eq!(px(center.0, center.1), plain.get_pixel(center.0, center.1).0);
+		assert!(px(edge.0, edge.1)[0] > plain.get_pixel(edge.0, edge.1).0[0] + 4);
+		assert_eq!(px(edge.0, edge.1)[1..3], [0, 0]);
+	}
+}
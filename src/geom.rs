@@ -1,28 +1,27 @@
 pub mod sphere {
 	#[repr(C)]
-	#[derive(Debug, Copy, Clone)]
+	#[derive(Debug, Copy, Clone, PartialEq)]
 	pub struct Sphere {
 		pub pos: [f32; 4],
-		pub col: [f32; 4],
+		pub orientation: [f32; 4], // Quaternion, rotates the texture around the center
 		pub r: f32,
-		pub reflexivity: f32,
-		pub diffuse_factor: f32,
-		pub texture_index: i32,
+		pub material_index: u32,
+		_pad: [u32; 2]
 	}
 
 	impl Sphere {
-		pub fn new(pos: [f32; 3], col: [f32; 4], r: f32, reflexivity: f32, diffuse_factor: f32, texture_index: i32) -> Self {
+		pub fn new(pos: [f32; 3], r: f32, material_index: u32) -> Self {
 			let pos = [pos[0], pos[1], pos[2], 0.0];
 			Self {
 				pos,
-				col,
+				orientation: [0.0, 0.0, 0.0, 1.0],
 				r,
-				reflexivity,
-				diffuse_factor,
-				texture_index
+				material_index,
+				_pad: [0; 2]
 			}
 		}
 	}
+	#[allow(dead_code)]
 	pub struct SphereIter {
 		len: u32,
 		r: f32,
@@ -30,6 +29,7 @@ pub mod sphere {
 	}
 
 	impl SphereIter {
+		#[allow(dead_code)]
 		pub fn new(len: u32, r: f32) -> Self {
 			Self {
 				len,
@@ -50,15 +50,11 @@ pub mod sphere {
 
 			let pos = [-(delta_dist) * (self.len as f32 / 2.0) + self.i as f32 * delta_dist, 0.0, 10.0];
 			
-			// let mut color = Vec4::new(pos[0].abs(), 0.0, pos[2] / 20.0, 1.0);
-			// color.normalize_mut();
-			let color = [1.0, 1.0, 1.0, 1.0];
-
 			println!("{:?}", pos);
 
 			self.i += 1;
 			
-			Some(Sphere::new(pos, color.into(), self.r, 0.5, 0.5, 0))
+			Some(Sphere::new(pos, self.r, 0))
 		}
 	}
 
@@ -70,45 +66,170 @@ pub mod sphere {
 }
 
 pub mod model {
-	use compute_vk::vulkano::pipeline::vertex;
-use stl_io;
+	use crate::stl;
+	use crate::uv::{self, UvProjection};
 	use tobj;
-	use std::fs;
-	use std::path::Path;
+	use std::collections::HashMap;
+	use std::path::{Path, PathBuf};
 	use std::fmt::Debug;
+	use nalgebra_glm::{Vec3, cross, dot};
 
 	#[repr(C)]
-	#[derive(Debug, Clone, Copy)]
+	#[derive(Debug, Clone, Copy, PartialEq)]
 	pub struct Model {
 		pub pos: [f32; 4],
-		pub col: [f32; 4],
-		pub reflexivity: f32,
-		pub diffuse_factor: f32,
+		pub rotation: [f32; 4], // Quaternion, from object space to world space
+		pub scale: [f32; 4], // Applied in object space before the rotation
 		pub indices_start: u32, // Index of the first indexed triangle of the model in the global indexed triangles array
 		pub indices_end: u32, // End of the indexed triangles
 		pub vertex_start: u32, // Index of the first vertex
 		pub vertex_end: u32, // Last vertex
-		pub texture_index: i32,
+		pub bvh_root: u32, // Index of the root node of the mesh in the BVH buffer
+		pub material_index: u32,
+		pub smooth_shading: u32, // 1 to interpolate the vertex normals, 0 to use the normal of the triangle
 		_pad: u32
 	}
 
 	impl Model {
-		pub fn from_obj<P: AsRef<Path> + Debug>(name: P, pos: [f32; 3], col: [f32; 4], reflexivity: f32, diffuse_factor: f32, texture_index: i32, vertices: &mut Vec<[f32; 4]>, uvs: &mut Vec<[f32; 2]>,  indices: &mut Vec<[u32; 4]>, normals: &mut Vec<[f32; 4]>) -> Self {
-			let pos = [pos[0], pos[1], pos[2], 0.0];
-			let vertices_offset = vertices.len() as u32;
-			let indices_start = indices.len() as u32;
+		/// Instance of the mesh, models created from the same mesh share its triangles
+		pub fn new(mesh: &Mesh, pos: [f32; 3], material_index: u32) -> Self {
+			Self {
+				pos: [pos[0], pos[1], pos[2], 0.0],
+				rotation: [0.0, 0.0, 0.0, 1.0],
+				scale: [1.0, 1.0, 1.0, 0.0],
+				indices_start: mesh.indices_start,
+				indices_end: mesh.indices_end,
+				vertex_start: mesh.vertex_start,
+				vertex_end: mesh.vertex_end,
+				bvh_root: mesh.bvh_root,
+				material_index,
+				smooth_shading: 1,
+				_pad: 0
+			}
+		}
+	}
+
+	/// Material read from the MTL file of an OBJ, texture paths are resolved relative to the OBJ
+	/// See `Material::from_mtl` for the conversion to the materials of the renderer
+	#[derive(Debug, Clone)]
+	pub struct ObjMaterial {
+		pub diffuse: [f32; 3], // Kd
+		pub specular: [f32; 3], // Ks
+		pub shininess: f32, // Ns
+		pub dissolve: f32, // d, opacity
+		pub optical_density: f32, // Ni, index of refraction
+		pub diffuse_texture: Option<PathBuf>, // map_Kd
+		pub bump_texture: Option<PathBuf>, // map_Bump
+	}
+
+	impl ObjMaterial {
+		fn new(m: &tobj::Material, obj_dir: &Path) -> Self {
+			let texture = |t: &str| -> Option<PathBuf> {
+				if t.is_empty() {
+					return None;
+				}
+
+				let resolved = resolve_texture_path(t, obj_dir);
+				if resolved.is_none() {
+					eprintln!("Warning: texture {} of material {} not found", t, m.name);
+				}
+				resolved
+			};
+
+			Self {
+				diffuse: m.diffuse,
+				specular: m.specular,
+				shininess: m.shininess,
+				dissolve: m.dissolve,
+				optical_density: m.optical_density,
+				diffuse_texture: texture(&m.diffuse_texture),
+				bump_texture: texture(&m.normal_texture)
+			}
+		}
+	}
+
+	/// Finds a texture referenced by a MTL file
+	/// Exporters often write absolute paths of the machine the model was made on (C:\\Users\\...),
+	/// so when the path doesn't exist its last components are searched from the folder of the OBJ and its two parents
+	fn resolve_texture_path(texture: &str, obj_dir: &Path) -> Option<PathBuf> {
+		let direct = obj_dir.join(texture);
+		if direct.is_file() {
+			return Some(direct);
+		}
+
+		let components: Vec<&str> = texture.split(['/', '\\'])
+			.filter(|c| !c.is_empty())
+			.collect();
+
+		let obj_dir = obj_dir.canonicalize().unwrap_or_else(|_| obj_dir.to_path_buf());
+		for dir in obj_dir.ancestors().take(3) {
+			for start in (0..components.len()).rev() {
+				let candidate: PathBuf = dir.join(components[start..].iter().collect::<PathBuf>());
+				if candidate.is_file() {
+					return Some(candidate);
+				}
+			}
+		}
+
+		None
+	}
+
+	/// Range of a loaded mesh in the global geometry buffers
+	#[derive(Debug, Clone, Copy)]
+	pub struct Mesh {
+		pub indices_start: u32, // Index of the first indexed triangle of the mesh in the global indexed triangles array
+		pub indices_end: u32, // End of the indexed triangles
+		pub vertex_start: u32, // Index of the first vertex
+		pub vertex_end: u32, // Last vertex
+		pub bvh_root: u32, // Set when the BVH of the mesh is built
+	}
 
-			let (models, _mats)  = tobj::load_obj(&name,
+	impl Mesh {
+		/// Loads every object of the OBJ file as its own mesh, with the material it uses in the MTL file
+		/// OBJ files without a mtllib statement use the .mtl file with the same name, if there is one
+		/// Objects without normals get generated ones using `options.crease_angle`, `options.uv_projection` replaces the UVs of every object
+		pub fn from_obj<P: AsRef<Path> + Debug>(name: P, vertices: &mut Vec<[f32; 4]>, uvs: &mut Vec<[f32; 2]>,  indices: &mut Vec<[u32; 4]>, normals: &mut Vec<[f32; 4]>, options: ImportOptions) -> Result<Vec<(Self, Option<ObjMaterial>)>, String> {
+			let (models, mats)  = tobj::load_obj(&name,
 				&tobj::LoadOptions {
 					single_index: true,
-					// triangulate: true,
+					triangulate: true, // Quads and polygons would break the triangle indices
 					// reorder_data: true,
 					.. Default::default()
 				}
-			).expect(&format!("Failed to load {:?}", name));
+			).map_err(|e| format!("Failed to load {:?}: {}", name, e))?;
+
+			let obj_dir = name.as_ref().parent().unwrap_or_else(|| Path::new(""));
+			let mut mats = match mats {
+				Ok(m) => m,
+				Err(e) => {
+					eprintln!("Warning: failed to load the materials of {:?}: {}", name, e);
+					Vec::new()
+				}
+			};
+
+			let mut fallback_material = None; // Applied to the objects without usemtl
+			if mats.is_empty() {
+				let mtl_path = name.as_ref().with_extension("mtl");
+				if mtl_path.is_file() {
+					match tobj::load_mtl(&mtl_path) {
+						Ok((m, _)) => {
+							if m.len() == 1 {
+								fallback_material = Some(0);
+							}
+							mats = m;
+						},
+						Err(e) => eprintln!("Warning: failed to load {}: {}", mtl_path.display(), e)
+					}
+				}
+			}
+
+			let materials: Vec<ObjMaterial> = mats.iter().map(|m| ObjMaterial::new(m, obj_dir)).collect();
 
+			let mut meshes = Vec::with_capacity(models.len());
 			for model in models {
 				let mesh = model.mesh;
+				let vertices_offset = vertices.len() as u32; // Indices of every object start at 0
+				let indices_start = indices.len() as u32;
 
 				assert_eq!(mesh.positions.len() % 3, 0);
 				assert_eq!(mesh.texcoords.len() % 2, 0);
@@ -147,92 +268,305 @@ use stl_io;
 						0.0
 					])
 				}
-			}			
 
-			let indices_end = indices.len() as u32; // used in a for loop for iterating over the triangles
+				// UVs and normals are indexed like the vertices, keep them aligned for objects missing them
+				uvs.resize(vertices.len(), [0.0; 2]);
+				normals.resize(vertices.len(), [0.0; 4]);
+				if mesh.normals.is_empty() {
+					generate_normals(vertices, uvs, &mut indices[indices_start as usize..], normals, options.crease_angle);
+				}
+				if let Some(projection) = &options.uv_projection {
+					uv::generate_uvs(vertices, uvs, normals, &mut indices[indices_start as usize..], projection);
+				}
+
+				let material = mesh.material_id
+					.or(fallback_material)
+					.and_then(|i| materials.get(i).cloned());
+
+				meshes.push((Self {
+					indices_start,
+					indices_end: indices.len() as u32,
+					vertex_start: vertices_offset,
+					vertex_end: vertices.len() as u32,
+					bvh_root: 0
+				}, material));
+			}
+
+			Ok(meshes)
+		}
+
+		/// Loads the triangles of the STL file, faces with their own colour in the attribute bytes are split into one mesh per colour
+		/// Vertices closer than `options.weld_epsilon` are merged, STL files store each triangle with its own vertices
+		/// STL files have no texture coordinates, every UV is 0 unless `options.uv_projection` generates them
+		pub fn from_stl<P: AsRef<Path>>(path: P, vertices: &mut Vec<[f32; 4]>, uvs: &mut Vec<[f32; 2]>, indices: &mut Vec<[u32; 4]>, normals: &mut Vec<[f32; 4]>, options: ImportOptions) -> Result<Vec<(Self, Option<[f32; 3]>)>, String> {
+			let path = path.as_ref();
+			let triangles = stl::read(path)?;
+
+			// Faces are grouped by colour in order of appearance, the faces without colour use the material of the model
+			let mut groups: Vec<(Option<[u32; 3]>, Vec<usize>)> = Vec::new();
+			for (i, t) in triangles.iter().enumerate() {
+				let key = t.color.map(|c| [c[0].to_bits(), c[1].to_bits(), c[2].to_bits()]);
+				match groups.iter_mut().find(|(k, _)| *k == key) {
+					Some((_, faces)) => faces.push(i),
+					None => groups.push((key, vec![i]))
+				}
+			}
+
+			let mut degenerate = 0;
+			let mut meshes = Vec::with_capacity(groups.len());
+			for (_, faces) in groups {
+				let vertices_offset = vertices.len() as u32;
+				let indices_start = indices.len() as u32;
+				let mut welder = Welder::new(options.weld_epsilon);
+
+				for &f in faces.iter() {
+					let mut tri = [0; 4];
+					for (corner, v) in triangles[f].vertices.iter().enumerate() {
+						tri[corner] = welder.index(*v, vertices);
+					}
+
+					let position = |i: u32| { let v = vertices[i as usize]; Vec3::new(v[0], v[1], v[2]) };
+					let a = position(tri[0]);
+					if cross(&(position(tri[1]) - a), &(position(tri[2]) - a)).norm() == 0.0 { // Also catches the corners welded together
+						degenerate += 1;
+						continue;
+					}
+					indices.push(tri);
+				}
+
+				uvs.resize(vertices.len(), [0.0; 2]);
+				normals.resize(vertices.len(), [0.0; 4]);
+				generate_normals(vertices, uvs, &mut indices[indices_start as usize..], normals, options.crease_angle);
+				if let Some(projection) = &options.uv_projection {
+					uv::generate_uvs(vertices, uvs, normals, &mut indices[indices_start as usize..], projection);
+				}
+
+				meshes.push((Self {
+					indices_start,
+					indices_end: indices.len() as u32,
+					vertex_start: vertices_offset,
+					vertex_end: vertices.len() as u32,
+					bvh_root: 0
+				}, triangles[faces[0]].color));
+			}
+
+			if degenerate > 0 {
+				eprintln!("Warning: {}: skipped {} degenerate triangles out of {}", path.display(), degenerate, triangles.len());
+			}
+
+			Ok(meshes)
+		}
+	}
+
+	/// Settings used when importing a mesh file
+	#[derive(Debug, Clone, Copy, PartialEq)]
+	pub struct ImportOptions {
+		pub crease_angle: f32, // Radians, see `generate_normals`
+		pub weld_epsilon: f32, // Largest distance between the STL vertices merged together, 0 only merges identical vertices
+		pub uv_projection: Option<UvProjection>, // Generates the UVs instead of reading them from the file
+	}
+
+	// Merges the vertices closer than epsilon, positions are hashed on a grid of cells epsilon wide
+	struct Welder {
+		epsilon: f32,
+		cells: HashMap<[i64; 3], Vec<u32>>,
+	}
 
+	impl Welder {
+		fn new(epsilon: f32) -> Self {
 			Self {
-				pos,
-				col,
-				reflexivity,
-				diffuse_factor,
-				indices_start,
-				indices_end,
-				vertex_start: vertices_offset,
-				vertex_end: vertices.len() as u32,
-				texture_index,
-				_pad: 0
+				epsilon,
+				cells: HashMap::new()
 			}
 		}
 
-		pub fn from_stl(name: &str, pos: [f32; 3], col: [f32; 4], reflexivity: f32, diffuse_factor: f32, texture_index: i32, vertices: &mut Vec<[f32; 4]>, uvs: &mut Vec<[f32; 2]>, indices: &mut Vec<[u32; 4]>, normals: &mut Vec<[f32; 4]>) -> Self {
-			let pos = [pos[0], pos[1], pos[2], 0.0];
-			let vertices_offset = vertices.len() as u32;
-			let indices_start = indices.len() as u32;
-	
-			let mut stl_file_reader = fs::File::open(name)
-				.expect(&format!("Failed to open {}", name));
-			let stl_file = stl_io::read_stl(&mut stl_file_reader)
-				.expect(&format!("Failed to read {}", name));
-			
-			normals.reserve(stl_file.vertices.len());
-
-			for vertex in stl_file.vertices {
-				vertices.push([
-					vertex[0],
-					vertex[1],
-					vertex[2],
-					0.0
-				]);
-
-				uvs.push([0.0, 0.0]);
-			}
-	
-			for indexed_tri in stl_file.faces {
-				let new_tri = [
-					indexed_tri.vertices[0] as u32 + vertices_offset,
-					indexed_tri.vertices[1] as u32 + vertices_offset,
-					indexed_tri.vertices[2] as u32 + vertices_offset,
-					0
-				];
-
-				// This assures compatibility with per-vertex-normal formats(Wavefront)
-				// Essentially copy the normal of the indexed_triangle for each of it's vertices
-				for i in new_tri.iter() {
-					let v = vertices[*i as usize];
-					let vi = vertices.iter().position(|&_v| _v == v).unwrap();
-
-					if vi >= normals.len() {
-						for j in 0..=(vi - normals.len()) { // grow the normals vector
-							normals.push([0.0; 4]);
+		fn cell(&self, v: [f32; 3]) -> [i64; 3] {
+			if self.epsilon > 0.0 {
+				[(v[0] / self.epsilon).floor() as i64, (v[1] / self.epsilon).floor() as i64, (v[2] / self.epsilon).floor() as i64]
+			} else {
+				[v[0].to_bits() as i64, v[1].to_bits() as i64, v[2].to_bits() as i64]
+			}
+		}
+
+		// Returns the index of a vertex at this position, pushing a new one if there is none
+		fn index(&mut self, v: [f32; 3], vertices: &mut Vec<[f32; 4]>) -> u32 {
+			let cell = self.cell(v);
+			let range = if self.epsilon > 0.0 { -1..=1 } else { 0..=0 }; // Close vertices can fall in neighbouring cells
+			for dx in range.clone() {
+				for dy in range.clone() {
+					for dz in range.clone() {
+						if let Some(candidates) = self.cells.get(&[cell[0] + dx, cell[1] + dy, cell[2] + dz]) {
+							for &i in candidates {
+								let w = vertices[i as usize];
+								if (0..3).all(|c| (w[c] - v[c]).abs() <= self.epsilon) {
+									return i;
+								}
+							}
 						}
 					}
+				}
+			}
 
-					normals[vi] = [
-						indexed_tri.normal[0],
-						indexed_tri.normal[1],
-						indexed_tri.normal[2],
-						0.0
-					];
+			vertices.push([v[0], v[1], v[2], 0.0]);
+			let i = (vertices.len() - 1) as u32;
+			self.cells.entry(cell).or_default().push(i);
+			i
+		}
+	}
+
+	/// Computes the vertex normals of `triangles`, the duplicated vertices are appended to the buffers so the mesh must be the last one loaded
+	/// Faces sharing a position are smoothed together unless the angle between them is larger than `crease_angle` (radians),
+	/// vertices on such creases are duplicated so each side keeps its own normal
+	fn generate_normals(vertices: &mut Vec<[f32; 4]>, uvs: &mut Vec<[f32; 2]>, triangles: &mut [[u32; 4]], normals: &mut Vec<[f32; 4]>, crease_angle: f32) {
+		let position = |v: [f32; 4]| Vec3::new(v[0], v[1], v[2]);
+		let position_key = |v: [f32; 4]| [v[0].to_bits(), v[1].to_bits(), v[2].to_bits()];
+
+		// Same as the flat normal of the shader, its length is twice the area of the face which weights the average
+		let face_normals: Vec<Vec3> = triangles.iter().map(|t| {
+			let a = position(vertices[t[0] as usize]);
+			cross(&(position(vertices[t[1] as usize]) - a), &(position(vertices[t[2] as usize]) - a))
+		}).collect();
+		let face_dirs: Vec<Option<Vec3>> = face_normals.iter().map(|n| if n.norm() > 0.0 { Some(n.normalize()) } else { None }).collect(); // None for the zero area faces
+
+		// Vertices with the same position often differ by their UVs, they still need to be smoothed together
+		let mut faces_at: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+		for (f, t) in triangles.iter().enumerate() {
+			for &v in t[..3].iter() {
+				let faces = faces_at.entry(position_key(vertices[v as usize])).or_default();
+				if !faces.contains(&f) {
+					faces.push(f);
 				}
-					
-				indices.push(new_tri);
 			}
-	
-			let indices_end = indices.len() as u32; // used in a for loop for iterating over the triangles
-	
-			Self {
-				pos,
-				col,
-				reflexivity,
-				diffuse_factor,
-				indices_start,
-				indices_end,
-				vertex_start: vertices_offset,
-				vertex_end: vertices.len() as u32,
-				texture_index,
-				_pad: 0
+		}
+
+		let cos_crease = crease_angle.cos();
+		let mut assigned: HashMap<u32, Vec<([f32; 4], u32)>> = HashMap::new(); // Normals given to each original vertex, with the vertex holding them
+		for (f, t) in triangles.iter_mut().enumerate() {
+			// A zero area face takes the direction of a face sharing one of its corners
+			let face_dir = face_dirs[f]
+				.or_else(|| t[..3].iter().flat_map(|v| faces_at[&position_key(vertices[*v as usize])].iter()).find_map(|&g| face_dirs[g]))
+				.unwrap_or_else(Vec3::z);
+
+			for v in t[..3].iter_mut() {
+				let mut sum = Vec3::zeros();
+				for &g in faces_at[&position_key(vertices[*v as usize])].iter() {
+					match face_dirs[g] {
+						Some(dir) if g == f || dot(&face_dir, &dir) >= cos_crease => sum += face_normals[g],
+						_ => ()
+					}
+				}
+
+				let n = if sum.norm() > 0.0 { sum.normalize() } else { face_dir };
+				let normal = [n.x, n.y, n.z, 0.0];
+
+				let copies = assigned.entry(*v).or_default();
+				if let Some(&(_, copy)) = copies.iter().find(|(other, _)| (0..3).all(|i| (other[i] - normal[i]).abs() < 1e-4)) {
+					*v = copy;
+				} else if copies.is_empty() {
+					normals[*v as usize] = normal;
+					copies.push((normal, *v));
+				} else { // Another face already gave a different normal to this vertex
+					vertices.push(vertices[*v as usize]);
+					uvs.push(uvs[*v as usize]);
+					normals.push(normal);
+					let copy = (vertices.len() - 1) as u32;
+					copies.push((normal, copy));
+					*v = copy;
+				}
 			}
 		}
 	}
-}
\ No newline at end of file
+	#[cfg(test)]
+	mod tests {
+		use super::*;
+
+		// Runs `generate_normals` on the triangles, returns the number of vertices after it and the normal of each corner
+		fn corner_normals(mut vertices: Vec<[f32; 4]>, mut triangles: Vec<[u32; 4]>, crease_angle: f32) -> (usize, Vec<Vec3>) {
+			let mut uvs = vec![[0.0; 2]; vertices.len()];
+			let mut normals = vec![[0.0; 4]; vertices.len()];
+			generate_normals(&mut vertices, &mut uvs, &mut triangles, &mut normals, crease_angle);
+			assert_eq!((uvs.len(), normals.len()), (vertices.len(), vertices.len()));
+			let corners = triangles.iter().flat_map(|t| t[..3].to_vec()).map(|v| Vec3::new(normals[v as usize][0], normals[v as usize][1], normals[v as usize][2])).collect();
+			(vertices.len(), corners)
+		}
+
+		// Triangles facing away from the origin
+		fn outward(vertices: &[[f32; 4]], triangles: &mut [[u32; 4]]) {
+			let position = |i: u32| { let v = vertices[i as usize]; Vec3::new(v[0], v[1], v[2]) };
+			for t in triangles.iter_mut() {
+				let n = cross(&(position(t[1]) - position(t[0])), &(position(t[2]) - position(t[0])));
+				if dot(&n, &(position(t[0]) + position(t[1]) + position(t[2]))) < 0.0 {
+					t.swap(1, 2);
+				}
+			}
+		}
+
+		#[test]
+		fn cube_corners_are_split() {
+			let vertices: Vec<[f32; 4]> = (0..8).map(|i| [(i & 1) as f32 * 2.0 - 1.0, (i >> 1 & 1) as f32 * 2.0 - 1.0, (i >> 2 & 1) as f32 * 2.0 - 1.0, 0.0]).collect();
+			let mut triangles = Vec::new();
+			for axis in 0..3 {
+				for side in 0..2 { // Corners of the face in order around it, from the bits of the two other axes
+					let (a, b) = (1 << ((axis + 1) % 3), 1 << ((axis + 2) % 3));
+					let base = side << axis;
+					triangles.push([base, base | a, base | a | b, 0]);
+					triangles.push([base, base | a | b, base | b, 0]);
+				}
+			}
+			outward(&vertices, &mut triangles);
+
+			let (vertex_count, corners) = corner_normals(vertices, triangles.clone(), 0.5);
+			assert_eq!(vertex_count, 24); // Each corner gets the normal of its 3 faces
+			for (face, n) in corners.chunks(6).zip(0..) { // The two triangles of each face, in the order they were pushed
+				let mut expected = Vec3::zeros();
+				expected[n / 2] = if n % 2 == 0 { -1.0 } else { 1.0 };
+				assert!(face.iter().all(|c| (c - expected).norm() < 1e-6), "{:?}", face);
+			}
+		}
+
+		#[test]
+		fn sphere_normals_are_shared() {
+			let (stacks, slices) = (8, 16);
+			let mut vertices = vec![[0.0, 1.0, 0.0, 0.0]];
+			for i in 1..stacks {
+				for j in 0..slices {
+					let (theta, phi) = (i as f32 * std::f32::consts::PI / stacks as f32, j as f32 * 2.0 * std::f32::consts::PI / slices as f32);
+					vertices.push([theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin(), 0.0]);
+				}
+			}
+			vertices.push([0.0, -1.0, 0.0, 0.0]);
+			let bottom = vertices.len() as u32 - 1;
+
+			let ring = |i: u32, j: u32| 1 + (i - 1) * slices + j % slices;
+			let mut triangles = Vec::new();
+			for j in 0..slices {
+				triangles.push([0, ring(1, j), ring(1, j + 1), 0]);
+				triangles.push([bottom, ring(stacks - 1, j), ring(stacks - 1, j + 1), 0]);
+				for i in 1..stacks - 1 {
+					triangles.push([ring(i, j), ring(i + 1, j), ring(i + 1, j + 1), 0]);
+					triangles.push([ring(i, j), ring(i + 1, j + 1), ring(i, j + 1), 0]);
+				}
+			}
+			outward(&vertices, &mut triangles);
+
+			let positions: Vec<Vec3> = triangles.iter().flat_map(|t| t[..3].to_vec()).map(|v| Vec3::new(vertices[v as usize][0], vertices[v as usize][1], vertices[v as usize][2])).collect();
+			let vertex_count = vertices.len();
+			let (count, corners) = corner_normals(vertices, triangles, 1.2);
+			assert_eq!(count, vertex_count); // Smoothed everywhere, no vertex is duplicated
+			for (n, p) in corners.iter().zip(positions.iter()) {
+				assert!(dot(n, p) > 0.99, "{:?} at {:?}", n, p);
+			}
+		}
+
+		#[test]
+		fn degenerate_faces_take_a_neighbour_normal() {
+			let vertices = vec![[0.0, 0.0, 0.0, 0.0], [1.0, 0.0, 0.0, 0.0], [1.0, 1.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [2.0, 0.0, 0.0, 0.0]];
+			// A quad facing +z, and a flat triangle along its bottom edge
+			let triangles = vec![[0, 1, 2, 0], [0, 2, 3, 0], [0, 1, 4, 0]];
+
+			for n in corner_normals(vertices, triangles, 0.5).1 {
+				assert!((n - Vec3::z()).norm() < 1e-6, "{:?}", n);
+			}
+		}
+	}
+}
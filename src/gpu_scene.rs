@@ -0,0 +1,158 @@
+// Scene buffers on the device, shared by the closures of the window
+// The scene is loaded again when its file or a file it references changes, and only the buffers whose content changed are rebuilt
+
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use compute_vk::{util, vulkano, watch::FileWatcher};
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer, DeviceLocalBuffer};
+use vulkano::device::{Device, Queue};
+
+use crate::animation::Animation;
+use crate::bvh::BvhNode;
+use crate::camera::Lens;
+use crate::geom::{sphere::Sphere, model::Model};
+use crate::light::{PointLight, DirectionalLight};
+use crate::material::Material;
+use crate::scene::Scene;
+use crate::texture::Texture;
+
+pub struct GpuScene {
+	pub spheres: Arc<CpuAccessibleBuffer<[Sphere]>>,
+	pub models: Arc<CpuAccessibleBuffer<[Model]>>,
+	pub vertices: Arc<CpuAccessibleBuffer<[[f32; 4]]>>,
+	pub uvs: Arc<DeviceLocalBuffer<[[f32; 2]]>>,
+	pub indices: Arc<DeviceLocalBuffer<[[u32; 4]]>>,
+	pub normals: Arc<CpuAccessibleBuffer<[[f32; 4]]>>,
+	pub bvh_nodes: Arc<DeviceLocalBuffer<[BvhNode]>>,
+	pub materials: Arc<CpuAccessibleBuffer<[Material]>>, // Host visible so materials can be edited at runtime
+	pub point_lights: Arc<CpuAccessibleBuffer<[PointLight]>>,
+	pub directional_lights: Arc<CpuAccessibleBuffer<[DirectionalLight]>>,
+	pub textures: Vec<Texture>,
+	pub animation: Animation,
+	pub lens: Lens,
+	scene: Scene, // Uploaded data, compared with the scene loaded again to find the buffers to rebuild
+	root: PathBuf, // Working directory when the scene was first loaded, the window changes it while it records
+	path: PathBuf, // Absolute like the watched files
+	watcher: Option<FileWatcher>,
+	device: Arc<Device>,
+	queue: Arc<Queue>,
+}
+
+// Usage of the buffers written by the animation every frame
+fn host_usage() -> BufferUsage {
+	BufferUsage {
+		transfer_destination: true,
+		storage_buffer: true,
+		.. BufferUsage::none()
+	}
+}
+
+impl GpuScene {
+	/// Uploads the scene loaded from `path`, its files are watched when `live_reload` is set
+	pub fn new(scene: Scene, path: PathBuf, live_reload: bool, device: Arc<Device>, queue: Arc<Queue>) -> Self {
+		let (d, q) = (device.clone(), queue.clone());
+		let root = env::current_dir().unwrap_or_default();
+		let path = root.join(path);
+		let watcher = if live_reload {
+			println!("Watching {} scene files", scene.sources.len());
+			Some(FileWatcher::new(scene.sources.iter().map(|f| root.join(f))))
+		} else {
+			None
+		};
+		Self {
+			spheres: util::build_cpu_buffer(d.clone(), host_usage(), scene.spheres.clone()).unwrap(),
+			models: util::build_cpu_buffer(d.clone(), BufferUsage::all(), scene.models.clone()).unwrap(),
+			vertices: util::build_cpu_buffer(d.clone(), BufferUsage::all(), scene.vertices.clone()).unwrap(),
+			uvs: util::build_local_buffer(d.clone(), q.clone(), BufferUsage::all(), scene.uvs.clone()).unwrap(),
+			indices: util::build_local_buffer(d.clone(), q.clone(), BufferUsage::all(), scene.indices.clone()).unwrap(),
+			normals: util::build_cpu_buffer(d.clone(), BufferUsage::all(), scene.normals.clone()).unwrap(),
+			bvh_nodes: util::build_local_buffer(d.clone(), q.clone(), BufferUsage::all(), scene.bvh_nodes.clone()).unwrap(),
+			materials: util::build_cpu_buffer(d.clone(), BufferUsage::all(), scene.materials.clone()).unwrap(),
+			point_lights: util::build_cpu_buffer(d.clone(), host_usage(), scene.point_lights.clone()).unwrap(),
+			directional_lights: util::build_cpu_buffer(d.clone(), host_usage(), scene.directional_lights.clone()).unwrap(),
+			textures: scene.textures.load_all(d, q),
+			animation: scene.animation.clone(),
+			lens: scene.camera.to_lens(),
+			scene,
+			root,
+			path,
+			watcher,
+			device,
+			queue
+		}
+	}
+
+	/// Loads the scene again when one of its files changed, returns true when the buffers changed and the descriptor set has to be rebuilt
+	/// The previous scene stays when the new one fails to load
+	pub fn poll(&mut self) -> bool {
+		let changed = match self.watcher.as_mut() {
+			Some(w) => w.poll(),
+			None => return false
+		};
+		if changed.is_empty() {
+			return false;
+		}
+
+		println!("{} changed, reloading the scene", changed[0].display());
+		let scene = match Scene::load(&self.path) {
+			Ok(s) => s,
+			Err(e) => {
+				eprintln!("{}", e);
+				return false;
+			}
+		};
+		if let Some(w) = self.watcher.as_mut() {
+			w.watch(scene.sources.iter().cloned());
+		}
+
+		let old = std::mem::replace(&mut self.scene, scene);
+		let new = &self.scene;
+		let (d, q) = (self.device.clone(), self.queue.clone());
+		let stale = old.animation.stale_buffers(&new.animation); // The animated buffers hold the values of the old tracks, the new ones overwrite theirs
+		let mut rebuilt = Vec::new();
+
+		if stale.spheres || new.spheres != old.spheres {
+			self.spheres = util::build_cpu_buffer(d.clone(), host_usage(), new.spheres.clone()).unwrap();
+			rebuilt.push("spheres");
+		}
+		if stale.models || new.models != old.models {
+			self.models = util::build_cpu_buffer(d.clone(), BufferUsage::all(), new.models.clone()).unwrap();
+			rebuilt.push("models");
+		}
+		if new.vertices != old.vertices || new.uvs != old.uvs || new.indices != old.indices || new.normals != old.normals || new.bvh_nodes != old.bvh_nodes {
+			self.vertices = util::build_cpu_buffer(d.clone(), BufferUsage::all(), new.vertices.clone()).unwrap();
+			self.uvs = util::build_local_buffer(d.clone(), q.clone(), BufferUsage::all(), new.uvs.clone()).unwrap();
+			self.indices = util::build_local_buffer(d.clone(), q.clone(), BufferUsage::all(), new.indices.clone()).unwrap();
+			self.normals = util::build_cpu_buffer(d.clone(), BufferUsage::all(), new.normals.clone()).unwrap();
+			self.bvh_nodes = util::build_local_buffer(d.clone(), q.clone(), BufferUsage::all(), new.bvh_nodes.clone()).unwrap();
+			rebuilt.push("meshes");
+		}
+		if stale.materials || new.materials != old.materials {
+			self.materials = util::build_cpu_buffer(d.clone(), BufferUsage::all(), new.materials.clone()).unwrap();
+			rebuilt.push("materials");
+		}
+		if stale.lights || new.point_lights != old.point_lights || new.directional_lights != old.directional_lights {
+			self.point_lights = util::build_cpu_buffer(d.clone(), host_usage(), new.point_lights.clone()).unwrap();
+			self.directional_lights = util::build_cpu_buffer(d.clone(), host_usage(), new.directional_lights.clone()).unwrap();
+			rebuilt.push("lights");
+		}
+		// Images are only compared by file, a texture file saved again is uploaded again
+		let textures = |s: &Scene| s.textures.paths().iter().map(|p| self.root.join(p)).collect::<Vec<_>>();
+		let new_textures = textures(new);
+		if new_textures != textures(&old) || changed.iter().any(|f| new_textures.contains(f)) { // The window compiles the shader again when their number changed
+			self.textures = new.textures.load_all(d, q);
+			rebuilt.push("textures");
+		}
+
+		self.animation = new.animation.clone();
+		self.lens = new.camera.to_lens();
+		if rebuilt.is_empty() {
+			println!("Scene reloaded, no buffer changed");
+		} else {
+			println!("Scene reloaded, rebuilt the {}", rebuilt.join(", "));
+		}
+		true // The lens and the animation may have changed, the update closure restarts
+	}
+}
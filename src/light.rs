@@ -1,6 +1,6 @@
 use nalgebra_glm::{Vec3, Vec4};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 #[repr(C)]
 pub struct PointLight {
 	pub pos: [f32; 4],
@@ -19,7 +19,7 @@ impl PointLight {
 	}
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 #[repr(C)]
 pub struct DirectionalLight {
 	pub direction: [f32; 4],
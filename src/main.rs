@@ -1,142 +1,143 @@
-use compute_vk::vulkano::command_buffer::CommandBuffer;
-use compute_vk::vulkano::sync::GpuFuture;
+#![allow(clippy::single_match, clippy::too_many_arguments, clippy::type_complexity)]
+
 use compute_vk::{self, loader, util, vulkano, winit, image};
-use winit::event::{Event, ElementState};
+use compute_vk::descriptor::DynamicDescriptorSet;
+use winit::event::Event;
 use nalgebra_glm::Vec3;
-use image::{GenericImageView};
 
 use vulkano::device::Device;
 use vulkano::device::Queue;
-use vulkano::format::Format;
 use vulkano::image::view::ImageView;
-use vulkano::image::{ImageDimensions, ImmutableImage, MipmapsCount, ImageUsage, ImageCreateFlags, ImageLayout};
-use vulkano::sampler::Sampler;
-use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
-use vulkano::descriptor::descriptor_set::UnsafeDescriptorSetLayout;
-use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
-use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::image::ImageDimensions;
+use vulkano::descriptor::descriptor_set::{DescriptorSetDesc, UnsafeDescriptorSetLayout};
 use winit::{dpi::PhysicalSize, event};
 
 use std::process::exit;
-use std::{f32::consts::PI, sync::Arc};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
-use crate::{geom::sphere::Sphere, quaternion::Quaternion};
+use crate::camera::PushConstants;
 
-mod ray;
 mod geom;
 mod light;
 mod camera;
 mod quaternion;
 mod texture;
+mod scene;
+mod cli;
+mod cpu;
+mod bvh;
+mod mesh;
+mod stl;
+mod material;
+mod uv;
+mod antialiasing;
+mod animation;
+mod gpu_scene;
+mod controller;
+mod camera_path;
 
 fn main() {
-    let scale = 1.0;
-    let camera_speed = 0.5;
-    let target_fps = 30.0;
-
-    let mut vertices = Vec::<[f32; 4]>::new();
-    let mut uvs = Vec::<[f32; 2]>::new();
-    let mut indices = Vec::<[u32; 4]>::new();
-    let mut normals = Vec::<[f32; 4]>::new();
-    
-    let models: Vec<geom::model::Model> = vec![
-        // geom::model::Model::from_stl("STL/cube.stl", [-5.0, 0.0, 10.0], [1.0, 1.0, 1.0, 1.0], 0.5, 0.5, -1, &mut vertices, &mut uvs, &mut indices, &mut normals),
-        // geom::model::Model::from_stl("STL/cube.stl", [2.0, 0.0, 10.0], [1.0, 1.0, 1.0, 1.0], 0.1, 0.9, &mut vertices, &mut indices, &mut normals),
-        // geom::model::Model::from_obj("OBJ/cube.obj", [2.0, 0.0, 10.0], [1.0, 1.0, 1.0, 0.0], 0.5, 0.5, 0, &mut vertices, &mut uvs, &mut indices, &mut normals),
-        // geom::model::Model::from_obj("OBJ/cube.obj", [-5.0, 0.0, 10.0], [1.0, 1.0, 1.0, 0.0], 0.5, 0.5, -1, &mut vertices, &mut uvs, &mut indices, &mut normals),
-        // geom::model::Model::from_obj("OBJ/uv_sphere.obj", [0.0, 0.0, 3.0], [1.0, 1.0, 1.0, 0.0], 0.5, 0.5, -1, &mut vertices, &mut uvs, &mut indices, &mut normals),
-        // geom::model::Model::from_obj("OBJ/quad.obj", [0.0, 0.0, 10.0], [1.0, 1.0, 1.0, 0.0], 0.5, 0.5, &mut vertices, &mut uvs, &mut indices, &mut normals),
-        // geom::model::Model::from_obj("OBJ/earth.obj", [0.0, 0.0, 10.0], [1.0, 1.0, 1.0, 0.0], 0.5, 0.5, -1, &mut vertices, &mut uvs, &mut indices, &mut normals),
-        // geom::model::Model::from_obj("OBJ/tri.obj", [0.0, 3.0, 10.0], [1.0, 1.0, 1.0, 0.0], 0.5, 0.5, &mut vertices, &mut uvs, &mut indices, &mut normals),
-        // geom::model::Model::new("STL/pyramid.stl", [-2.0, -1.0, 10.0], [1.0, 1.0, 1.0, 1.0], 0.9, 0.1, &mut vertices, &mut indices),
-        // geom::model::Model::new("STL/monkey.stl", [0.0, 1.0, 8.0], [1.0, 1.0, 1.0, 1.0], 0.5, 0.5, &mut vertices, &mut indices),
-        // geom::model::Model::from_stl("STL/ground.stl", [0.0, -1.0, 10.0], [0.0, 1.0, 0.0, 1.0], 0.5, 0.5, -1, &mut vertices, &mut uvs, &mut indices, &mut normals),
-    ];
-
-    dbg!(normals.len());
-    dbg!(indices.len());
-    dbg!(vertices.len());
-    dbg!(uvs.len());
-    
-    // dbg!(&normals);
-
-    /*
-    let models = vec![
-        geom::model::Model {
-            pos: [0.0, 0.0, 10.0, 0.0],
-            indices_start: 0,
-            indices_end: 2,
-            _pad: [0; 2]
-        }
-    ];
+    let options = cli::Options::parse();
 
-    vertices.push([-1.0, 1.0, 0.0, 0.0]);
-    vertices.push([-1.0, -1.0, 0.0, 0.0]);
-    vertices.push([10.0, -1.0, 0.0, 0.0]);
-    vertices.push([10.0, 1.0, 0.0, 0.0]);
-    indices.push([0, 1, 2, 0]);
-    indices.push([0, 2, 3, 0]);
-    */
+    if options.list_devices {
+        for (i, name, ty) in util::list_devices(&vulkano::app_info_from_cargo_toml!()) {
+            println!("{}: {} ({})", i, name, ty);
+        }
+        return;
+    }
+
+    let scale = options.scale;
+    let target_fps = options.fps;
+    let view = options.view();
+    let path_tracing = options.path_tracing;
+    let aa = options.anti_aliasing();
+
+    let scene = match scene::Scene::load(&options.scene) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
 
+    for (mesh, stats) in scene.mesh_stats.iter() {
+        println!("{} BVH: {}", mesh, stats);
+    }
+
+    if options.cpu {
+        let mut scene = scene;
+        // Frame 0 of the animation, like the first frame of --headless
+        scene.animation.apply(0.0, &mut animation::SceneBuffers {
+            spheres: &mut scene.spheres,
+            models: &mut scene.models,
+            point_lights: &mut scene.point_lights,
+            directional_lights: &mut scene.directional_lights,
+            materials: &mut scene.materials
+        });
+        let renderer = cpu::CpuRenderer::new(&scene);
+
+        let size = PhysicalSize::new(((options.width as f32 * scale) as u32).max(1), ((options.height as f32 * scale) as u32).max(1));
+        let start = std::time::Instant::now();
+        let frames = if aa.accumulate { options.samples } else { 1 };
+        let (mut camera, mut view) = (scene.camera.to_camera(), view);
+        scene.animation.apply_camera(0.0, &mut camera, &mut view.fov);
+        view.clamp_fov();
+        let img = renderer.render(size, &view, &camera, &scene.camera.to_lens(), &aa, path_tracing, frames);
+        println!("Rendered on the CPU in {}s", start.elapsed().as_secs_f32());
+
+        let path = options.output_dir.join("0.png");
+        if let Err(e) = std::fs::create_dir_all(&options.output_dir).map_err(|e| e.to_string()).and_then(|_| img.save(&path).map_err(|e| e.to_string())) {
+            eprintln!("Failed to save {}: {}", path.display(), e);
+            exit(1);
+        }
+        return;
+    }
+
+    let texture_count = scene.textures.slot_count();
+    let scene_camera = scene.camera;
+
+    let key_map = match &options.key_map {
+        Some(path) => controller::KeyMap::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        }),
+        None => controller::KeyMap::default()
+    };
+    // Shared by the update closures, so the camera stays where it is when the window is resized
+    let controller = Rc::new(RefCell::new(controller::CameraController::new(
+        Vec3::from(scene_camera.pos), scene_camera.yaw.to_radians(), scene_camera.pitch.to_radians(), options.camera_speed, key_map
+    )));
+    let cursor_grab = controller.borrow().cursor_grab();
+
+    let player = match camera_path::PathPlayer::new(options.camera_path.clone(), options.record_path) {
+        Ok(p) => Rc::new(RefCell::new(p)),
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
+    if options.play {
+        player.borrow_mut().toggle(0.0);
+    }
+    let recording = player.borrow().recording();
+
+    dbg!(scene.normals.len());
+    dbg!(scene.indices.len());
+    dbg!(scene.vertices.len());
+    dbg!(scene.uvs.len());
+
+    let scene_path = options.scene.clone();
+    let live_reload = !options.headless; // The window reloads the scene when its files change
+    let shader_rebuild = Arc::new(Mutex::new(None)); // Set when a reloaded scene has another number of textures
+    let window_shader_rebuild = shader_rebuild.clone();
     let ds_builder = move |_size: PhysicalSize<u32>, _device: Arc<Device>, _queue: Arc<Queue>, _layout: Arc<UnsafeDescriptorSetLayout>| {
-        let _size = PhysicalSize::new((_size.width as f32 * scale) as u32, (_size.height as f32 * scale) as u32);
-        let bu = BufferUsage {
-            transfer_destination: true,
-            storage_buffer: true,
-            .. BufferUsage::none()
-        };
-
-        let sphere_buffer = {
-            let mut spheres: Vec<Sphere> = vec![
-                Sphere::new([0.0, 0.0, 20.0], [0.0, 0.0, 1.0, 1.0], 2.0, 0.5, 0.5, 1),
-            ];
-
-            
-            // let s = 10;
-            // for i in 0..s {
-            //     let angle = i as f32 * (2.0 * PI / s as f32);
-            //     spheres.push(Sphere::new([angle.cos() * 4.0, 0.0, angle.sin() * 4.0 + 20.0], [1.0, 1.0, 1.0, 1.0], 0.5, 0.5, 0.5, -1));
-            // }
-            
-    
-            util::build_cpu_buffer(_device.clone(), bu, spheres).unwrap()
-        };
-
-        let model_buffer = {
-            util::build_cpu_buffer(_device.clone(), BufferUsage::all(), models).unwrap()
-        };
-
-        let vertex_buffer = util::build_cpu_buffer(_device.clone(), BufferUsage::all(), vertices).unwrap();
-        let uv_buffer = util::build_local_buffer(_device.clone(), _queue.clone(), BufferUsage::all(), uvs).unwrap();
-        let indice_buffer = util::build_local_buffer(_device.clone(), _queue.clone(), BufferUsage::all(), indices).unwrap();
-        let normal_buffer = util::build_cpu_buffer(_device.clone(), BufferUsage::all(), normals).unwrap();
-
-        let light_buffer = {
-            let lights: Vec::<light::PointLight> = vec![
-                // light::PointLight::new(Vec3::new(0.0, 10.0, 10.0), Vec3::new(1.0, 1.0, 1.0), 3.0),
-                // light::PointLight::new(Vec3::new(-15.0, 10.0, 0.0), Vec3::new(1.0, 1.0, 1.0), 200.0),
-                // light::PointLight::new(Vec3::new(-20.0, 0.0, 10.0), Vec3::new(1.0, 1.0, 1.0), 200.0),
-                // light::PointLight::new(Vec3::new(0.0, 10.0, 5.0), Vec3::new(0.0, 0.0, 1.0), 20.0),
-                // light::PointLight::new(Vec3::new(10.0, 10.0, 10.0), Vec3::new(0.0, 1.0, 0.0), 100.0),
-            ];
-
-            util::build_cpu_buffer(_device.clone(), bu, lights).unwrap()
-        };
-
-        let dir_light_buffer = {
-            let dir_lights: Vec::<light::DirectionalLight> = vec![
-                // light::DirectionalLight::new(Vec3::new(-1.0, -1.5, 1.3).normalize(), Vec3::new(1.0, 0.0, 1.0), 1.0),
-                light::DirectionalLight::new(Vec3::new(0.0, -1.0, 0.0).normalize(), Vec3::new(1.0, 1.0, 1.0), 1.0),
-                // light::DirectionalLight::new(Vec3::new(0.0, 0.0, -1.0).normalize(), Vec3::new(1.0, 1.0, 1.0), 1.0),
-            ];
-
-            util::build_cpu_buffer(_device.clone(), bu, dir_lights).unwrap()
-        };
-
-        let (bw_texture_view, bw_texture_sampler) = texture::load_texture("Images/UgandanKnuckles.png", _device.clone(), _queue.clone());
-        let (base_texture_view, base_texture_sampler) = texture::load_texture("Images/earth.jpg", _device.clone(), _queue.clone());
+        // Shared by the resize closures, so the buffers of a reloaded scene are used by the next descriptor sets
+        let gpu_scene = Rc::new(RefCell::new(gpu_scene::GpuScene::new(scene, scene_path, live_reload, _device.clone(), _queue.clone())));
 
-        let resize = move |_size: PhysicalSize<u32>, _device: Arc<Device>, _queue: Arc<Queue>, _layout| {
+        let resize = move |_size: PhysicalSize<u32>, _device: Arc<Device>, _queue: Arc<Queue>, _layout: Arc<UnsafeDescriptorSetLayout>| {
+            let _size = PhysicalSize::new(((_size.width as f32 * scale) as u32).max(1), ((_size.height as f32 * scale) as u32).max(1));
             let output_img = util::build_image(_device.clone(), _queue.clone(),
                 ImageDimensions::Dim2d { width: _size.width, height: _size.height, array_layers: 1 },
                 vulkano::format::Format::B8G8R8A8Unorm
@@ -144,160 +145,128 @@ fn main() {
 
             let output_img_view = ImageView::new(output_img.clone()).unwrap();
 
-            let ray_buffer = {
-                let rays = ray::RayGen::new(_size, std::f32::consts::PI * 0.5).generate();
-                util::build_local_buffer(_device.clone(), _queue.clone(), BufferUsage::all(), rays).unwrap()
-            };
-
-            let ds = PersistentDescriptorSet::start(_layout)
-                .add_image(output_img_view).unwrap()
-                .add_buffer(ray_buffer.clone()).unwrap()
-                .add_buffer(sphere_buffer.clone()).unwrap()
-                .add_buffer(model_buffer.clone()).unwrap()
-                .add_buffer(vertex_buffer.clone()).unwrap()
-                .add_buffer(uv_buffer.clone()).unwrap()
-                .add_buffer(indice_buffer.clone()).unwrap()
-                .add_buffer(normal_buffer.clone()).unwrap()
-                .add_buffer(light_buffer.clone()).unwrap()
-                .add_buffer(dir_light_buffer.clone()).unwrap()
-                .enter_array().unwrap()
-                .add_sampled_image(bw_texture_view.clone(), bw_texture_sampler.clone()).unwrap()
-                .add_sampled_image(base_texture_view.clone(), base_texture_sampler.clone()).unwrap()
-                .leave_array().unwrap()
-                // .add_sampled_image(texture_view, sampler).unwrap()
+            // Float so the average doesn't lose precision, a new image starts a new average
+            let accumulation_img = util::build_image(_device.clone(), _queue.clone(),
+                ImageDimensions::Dim2d { width: _size.width, height: _size.height, array_layers: 1 },
+                vulkano::format::Format::R32G32B32A32Sfloat
+            );
+            let accumulation_img_view = ImageView::new(accumulation_img).unwrap();
+
+            // The shader keeps its number of textures until it compiles with the one of a reloaded scene
+            let texture_slots = _layout.descriptor(TEXTURES_BINDING).map_or(1, |d| d.array_count as usize);
+            let gpu = gpu_scene.borrow();
+            let textures = gpu.textures.iter().cloned().cycle().take(texture_slots).collect();
+            let ds = DynamicDescriptorSet::start(_layout)
+                .add_image(output_img_view)
+                .add_buffer(gpu.spheres.clone())
+                .add_buffer(gpu.models.clone())
+                .add_buffer(gpu.vertices.clone())
+                .add_buffer(gpu.uvs.clone())
+                .add_buffer(gpu.indices.clone())
+                .add_buffer(gpu.normals.clone())
+                .add_buffer(gpu.point_lights.clone())
+                .add_buffer(gpu.directional_lights.clone())
+                .add_sampled_image_array(textures).unwrap()
+                .add_buffer(gpu.bvh_nodes.clone())
+                .add_buffer(gpu.materials.clone())
+                .add_image(accumulation_img_view)
                 .build().unwrap();
 
-            let dispatch = [_size.width / 8, _size.width / 8, 1];
-
-            let mut y_angle = 0.0;
-            let mut x_angle = 0.0;
-
-            let mut camera = camera::Camera { // Used as push constant
-                pos: [0.0, 0.0, 0.0, 0.0],
-                orientation: [0.0, 0.0, 0.0, 1.0]
-            };
-
-            let sb = sphere_buffer.clone();
-            let lb = light_buffer.clone();
-            let vb = vertex_buffer.clone();
-            let mb = model_buffer.clone();
-            let nb = normal_buffer.clone();
-
-            let update = move |ev: Option<&Event<()>>, t: f64| {
+            let dispatch = [_size.width.div_ceil(8), _size.height.div_ceil(8), 1]; // Rounded up, the shader discards the invocations outside of the image
+
+            let controller = controller.clone();
+            let player = player.clone();
+            let lens = gpu.lens;
+            let mut view = view;
+            let mut samples = 0; // Frames accumulated since the camera or the scene last changed
+
+            let sb = gpu.spheres.clone();
+            let _mb = gpu.models.clone();
+            let _lb = gpu.point_lights.clone();
+            let _dlb = gpu.directional_lights.clone();
+            let _matb = gpu.materials.clone();
+            let animation = gpu.animation.clone();
+            drop(gpu);
+            let gpu_scene = gpu_scene.clone();
+            let shader_rebuild = shader_rebuild.clone();
+
+            let update = move |ev: Option<&Event<()>>, _t: f64| {
                 let ev = match ev {
                     Some(e) => e,
-                    None => return (camera, false)
+                    None => return (PushConstants::new(controller.borrow().camera(), lens, view, samples), false)
                 };
 
+                let key_down = controller.borrow().key_down(ev);
+                if let Some(key) = key_down {
+                    let key_map = controller.borrow().key_map().clone();
+                    if key == key_map.projection {
+                        let projection = view.projection().next();
+                        println!("Projection: {:?}", projection);
+                        view.projection = projection as u32;
+                        view.clamp_fov(); // The fisheye and equirectangular fovs can be wider than a perspective can show
+                        samples = 0;
+                    } else if key == key_map.keyframe {
+                        player.borrow_mut().add_keyframe(controller.borrow().camera(), view.fov);
+                    } else if key == key_map.play {
+                        player.borrow_mut().toggle(_t);
+                        samples = 0;
+                    }
+                }
+
+                if controller.borrow_mut().handle_event(ev) {
+                    samples = 0;
+                }
+
                 match ev {
-                    event::Event::DeviceEvent { event, .. } => {
-                        let mut camera_movement = Vec3::new(0.0, 0.0, 0.0);
-                        match event {
-                            event::DeviceEvent::MouseMotion { delta } => {
-                                x_angle += delta.1 as f32 * 0.001;
-                                y_angle += delta.0 as f32 * 0.001;
-                            },
-                            event::DeviceEvent::Key(kb_input) => {
-                                dbg!(kb_input.scancode);
-                                
-                                match kb_input.scancode {
-                                    17 => camera_movement.z += camera_speed, // W
-                                    31 => camera_movement.z -= camera_speed, // S
-                                    30 => camera_movement.x -= camera_speed, // A
-                                    32 => camera_movement.x += camera_speed, // D
-                                    57 => camera_movement.y += camera_speed, // Space
-                                    29 => camera_movement.y -= camera_speed, // CTRL
-                                    _ => ()
-                                }
+                    event::Event::RedrawEventsCleared => { // Animation things
+                        if gpu_scene.borrow_mut().poll() { // Rebuilds the descriptor set and restarts this closure with the new buffers
+                            let texture_count = gpu_scene.borrow().textures.len();
+                            if texture_count != texture_slots { // The canvas compiles the shader again before the descriptor set is rebuilt
+                                *shader_rebuild.lock().unwrap() = Some((shader_layout(texture_count), shader_defines(texture_count, path_tracing, &aa)));
                             }
-                            _ => ()
+                            return (PushConstants::new(controller.borrow().camera(), lens, view, samples), true);
                         }
-
-                        // Update camera
-                        let x_axis = Quaternion::from_axis(Vec3::new(1.0, 0.0, 0.0), x_angle);
-                        let y_axis = Quaternion::from_axis(Vec3::new(0.0, 1.0, 0.0), y_angle);
-                        let camera_quat = y_axis * x_axis;
-                        camera.orientation = camera_quat.into(); // Defining camera orientation quaternion
-                        let camera_vel = camera_quat.transform_point(camera_movement); // Moving the camera in its looking direction
-                        camera.pos[0] += camera_vel.x;
-                        camera.pos[1] += camera_vel.y;
-                        camera.pos[2] += camera_vel.z;
-                    },
-                    event::Event::RedrawEventsCleared => { // Animation things
-                        let r = Quaternion::from_axis(Vec3::new(0.0, 1.0, 0.0).normalize(), 0.25 * PI / target_fps as f32);
-                        let rs = Quaternion::new(0.0, 0.0, 0.0, 1.0);
-                        match sb.write() {
-                            Ok(mut _sb) => {
-                                for i in 1.._sb.len() {
-                                    let mut pos = Vec3::new(_sb[i].pos[0], _sb[i].pos[1], _sb[i].pos[2]);
-                                    pos = rs.transform_around(pos, [0.0, 0.0, 20.0].into());
-                                    _sb[i].pos = [pos.x, pos.y, pos.z, 0.0];
-                                }
-                            },
-                            _ => ()
+                        if controller.borrow_mut().update() {
+                            samples = 0;
                         }
-
-                        match lb.write() {
-                            Ok(mut _lb) => {
-                                /*
-                                let r = Quaternion::from_axis([0.0, 1.0, 0.0].into(), 0.001);
-
-                                let mut pos = Vec3::new(lb[1].pos[0], lb[1].pos[1], lb[1].pos[2]);
-                                pos = r.transform_around(pos, [0.0, 0.0, 20.0].into());
-
-                                lb[1].pos = [
-                                    pos.x,
-                                    pos.y,
-                                    pos.z + 20.0,
-                                    0.0
-                                ];
-                                */
-                            },
-                            _ => ()
+                        let mut camera = controller.borrow().camera();
+                        let mut view = view;
+                        if animation.animates_camera() { // Drivers move the controlled camera, keyframes replace it
+                            animation.apply_camera(_t as f32, &mut camera, &mut view.fov);
+                            samples = 0;
+                        }
+                        if let Some((path_camera, fov)) = player.borrow_mut().update(_t) { // The camera path overrides the controller while it plays
+                            camera = path_camera;
+                            view.fov = fov;
+                            samples = 0;
+                        }
+                        view.clamp_fov();
+
+                        // Animated objects, lights and materials, the values are computed from t so they don't drift
+                        if animation.animates_scene() {
+                            if let (Ok(mut _sb), Ok(mut _mb), Ok(mut _lb), Ok(mut _dlb), Ok(mut _matb)) = (sb.write(), _mb.write(), _lb.write(), _dlb.write(), _matb.write()) {
+                                animation.apply(_t as f32, &mut animation::SceneBuffers {
+                                    spheres: &mut _sb[..],
+                                    models: &mut _mb[..],
+                                    point_lights: &mut _lb[..],
+                                    directional_lights: &mut _dlb[..],
+                                    materials: &mut _matb[..]
+                                });
+                            }
+                            samples = 0;
                         }
 
-                        // match mb.read() {
-                        //     Ok(_mb) => {
-                        //         let start = _mb[0].vertex_start as usize;
-                        //         let end = _mb[0].vertex_end as usize;
-
-                        //         match (vb.write()) {
-                        //             Ok(mut _vb) => {
-                        //                 for i in start..end {
-                        //                     let tv = r.transform_around([_vb[i][0], _vb[i][1], _vb[i][2]].into(), [0.0, 0.0, 0.0].into());
-                        //                     _vb[i] = [
-                        //                         tv.x,
-                        //                         tv.y,
-                        //                         tv.z,
-                        //                         0.0
-                        //                     ];
-                        //                 }
-                        //             },
-                        //             _ => ()
-                        //         }
-
-                        //         match nb.write() {
-                        //             Ok(mut _nb) => {
-                        //                 for i in start..end {
-                        //                     let tn = r.transform_around([_nb[i][0], _nb[i][1], _nb[i][2]].into(), [0.0, 0.0, 0.0].into());
-                        //                     _nb[i] = [
-                        //                         tn.x,
-                        //                         tn.y,
-                        //                         tn.z,
-                        //                         0.0
-                        //                     ];
-                        //                 }
-                        //             },
-                        //             _ => ()
-                        //         }
-                        //     }
-                        //     _ => ()
-                        // }                        
+                        let push_constants = PushConstants::new(camera, lens, view, samples);
+                        samples += 1;
+                        if aa.accumulate && samples.is_power_of_two() {
+                            println!("Accumulated {} frames, {} samples per pixel", samples, samples * aa.samples);
+                        }
+                        return (push_constants, false);
                     },
                     _ => (),
                 }
 
-                (camera, false)
+                (PushConstants::new(controller.borrow().camera(), lens, view, samples), false)
             };
 
             (Arc::new(ds), output_img, dispatch, update)
@@ -309,10 +278,39 @@ fn main() {
         (ds, output_img, dispatch, update, resize)
     };
 
-    let win_size = PhysicalSize::new(800, 600);
+    let win_size = PhysicalSize::new(options.width, options.height);
+    let app_info = vulkano::app_info_from_cargo_toml!();
+    let shader_path = options.shader_dir.join("ray3d.glsl");
+
+    if options.headless {
+        let mut headless = compute_vk::headless::Headless::new(win_size, ds_builder, &app_info, options.device);
+
+        let shader = loader::Shader::load_with_defines(headless.device.clone(), &shader_path.to_string_lossy(), shader_layout(texture_count), &shader_defines(texture_count, path_tracing, &aa))
+            .expect("Failed to load the shader");
+        headless.set_shader(shader);
+
+        let t_step = options.t_step.unwrap_or(1.0 / target_fps);
+        headless.run(options.frames, t_step, &options.output_dir).unwrap();
+    } else {
+        let mut canvas = compute_vk::canvas::Canvas::new(win_size, ds_builder, &app_info, options.device);
+        canvas.set_capture_dir(&options.output_dir);
+        canvas.set_cursor_grab(cursor_grab);
+        canvas.set_recording(recording);
+        canvas.set_shader_rebuild(window_shader_rebuild);
+
+        let shader = loader::Shader::load_with_defines(canvas.device.clone(), &shader_path.to_string_lossy(), shader_layout(texture_count), &shader_defines(texture_count, path_tracing, &aa))
+            .expect("Failed to load the shader");
+        canvas.set_shader(shader);
 
-    let mut canvas = compute_vk::canvas::Canvas::new(win_size, ds_builder, &vulkano::app_info_from_cargo_toml!());
-    
+        canvas.run(target_fps).unwrap();
+    }
+}
+
+/// Binding of the textures array in `shader_layout`
+const TEXTURES_BINDING: usize = 9;
+
+/// Describes the bindings declared in ray3d.glsl
+fn shader_layout(texture_count: usize) -> loader::MainLayout {
     let mut shader_layout = loader::MainLayout::new();
     shader_layout.add_image(0);
     shader_layout.add_buffer(0, false);
@@ -323,13 +321,20 @@ fn main() {
     shader_layout.add_buffer(0, false);
     shader_layout.add_buffer(0, false);
     shader_layout.add_buffer(0, false);
-    shader_layout.add_buffer(0, false);
-    shader_layout.add_sampled_image_array(0, 2, true);
-    shader_layout.add_push_constant_range(0, 32);
-
-    let shader = loader::Shader::load(canvas.device.clone(), "shader/ray3d.glsl", shader_layout)
-        .expect("Failed to load the shader");
-    canvas.set_shader(shader);
+    shader_layout.add_sampled_image_array(0, texture_count as u32, true);
+    shader_layout.add_buffer(0, false); // BVH nodes
+    shader_layout.add_buffer(0, false); // Materials
+    shader_layout.add_image(0); // Accumulation
+    shader_layout.add_push_constant_range(0, std::mem::size_of::<PushConstants>());
+    shader_layout
+}
 
-    canvas.run(target_fps).unwrap();
+/// Sizes of the arrays of ray3d.glsl which depend on the scene, and the rendering mode
+fn shader_defines(texture_count: usize, path_tracing: bool, aa: &antialiasing::AntiAliasing) -> Vec<(&'static str, String)> {
+    let mut defines = vec![("TEXTURE_COUNT", texture_count.to_string())];
+    if path_tracing {
+        defines.push(("PATH_TRACING", String::from("1")));
+    }
+    defines.extend(aa.defines());
+    defines
 }
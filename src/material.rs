@@ -0,0 +1,56 @@
+// Surface properties shared by spheres and models, stored in their own buffer and referenced by index
+// Adding a property only changes this struct and its GLSL twin in ray3d.glsl
+
+use crate::geom::model::ObjMaterial;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(C)]
+pub struct Material {
+	pub col: [f32; 4], // Albedo, used when there is no texture
+	pub emission: [f32; 4], // Light emitted by the surface, added to the reflected light
+	pub absorption: [f32; 4], // Beer-Lambert coefficients, fraction of the light absorbed per unit of distance travelled inside
+	pub texture_index: i32, // -1 for no texture
+	pub normal_texture_index: i32, // -1 for no normal map
+	pub reflexivity: f32, // When computing reflections, factor of the incoming light reflected
+	pub diffuse_factor: f32, // When computing reflections, factor of the added diffuse light to the incoming reflected light
+	pub roughness: f32,
+	pub metallic: f32,
+	pub ior: f32, // Index of refraction
+	pub opacity: f32, // The rest of the light is split between reflection and refraction by the Fresnel equations
+}
+
+impl Default for Material {
+	fn default() -> Self {
+		Self {
+			col: [1.0, 1.0, 1.0, 1.0],
+			emission: [0.0; 4],
+			absorption: [0.0; 4],
+			texture_index: -1,
+			normal_texture_index: -1,
+			reflexivity: 0.5,
+			diffuse_factor: 0.5,
+			roughness: 0.0,
+			metallic: 0.0,
+			ior: 1.0,
+			opacity: 1.0
+		}
+	}
+}
+
+impl Material {
+	/// Maps a MTL material onto the renderer's, the texture indices come from the caller which owns the texture list
+	pub fn from_mtl(m: &ObjMaterial, texture_index: i32, normal_texture_index: i32) -> Self {
+		let reflexivity = (m.specular[0] + m.specular[1] + m.specular[2]) / 3.0;
+		Self {
+			col: [m.diffuse[0], m.diffuse[1], m.diffuse[2], 1.0],
+			texture_index,
+			normal_texture_index,
+			reflexivity,
+			diffuse_factor: 1.0 - reflexivity, // MTL files have no diffuse factor, the light that isn't reflected is diffused
+			roughness: (2.0 / (m.shininess + 2.0)).sqrt(), // Inverse of the usual Blinn-Phong exponent to roughness conversion
+			ior: if m.optical_density > 0.0 { m.optical_density } else { 1.0 },
+			opacity: m.dissolve,
+			..Self::default()
+		}
+	}
+}
@@ -0,0 +1,132 @@
+// Loads every mesh file once, the models using the same file are instances sharing its triangles and BVH
+// Keeps the size of the geometry buffers proportional to the number of unique meshes
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::bvh::{self, BvhNode, BvhStats};
+use crate::geom::model::{ImportOptions, Mesh, ObjMaterial};
+
+/// Part of a mesh file using a single material, OBJ files have one per object and STL files one per face colour
+#[derive(Debug, Clone)]
+pub struct SubMesh {
+	pub mesh: Mesh,
+	pub material: Option<ObjMaterial>,
+	pub color: Option<[f32; 3]>, // Colour of the faces of STL files, applied on top of the material of the model
+}
+
+#[derive(Default)]
+pub struct MeshRegistry {
+	ids: HashMap<PathBuf, Vec<(ImportOptions, usize)>>, // Canonical path to the options the file was imported with and index in `files`
+	files: Vec<(PathBuf, Vec<SubMesh>)>,
+	stats: Vec<(String, BvhStats)>,
+
+	// Global buffers uploaded to the shader
+	pub vertices: Vec<[f32; 4]>,
+	pub uvs: Vec<[f32; 2]>,
+	pub indices: Vec<[u32; 4]>,
+	pub normals: Vec<[f32; 4]>,
+	pub bvh_nodes: Vec<BvhNode>,
+}
+
+impl MeshRegistry {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Returns the sub meshes stored in the file, the file is only loaded the first time
+	/// A file imported with different options is loaded again
+	pub fn load<P: AsRef<Path>>(&mut self, path: P, options: ImportOptions) -> Result<Vec<SubMesh>, String> {
+		let path = path.as_ref();
+		let key = path.canonicalize()
+			.map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+		if let Some(&(_, id)) = self.ids.get(&key).and_then(|imports| imports.iter().find(|(o, _)| *o == options)) {
+			return Ok(self.files[id].1.clone());
+		}
+
+		let ext = path.extension()
+			.and_then(|e| e.to_str())
+			.map(|e| e.to_lowercase());
+
+		let parts: Vec<(Mesh, Option<ObjMaterial>, Option<[f32; 3]>)> = match ext.as_deref() {
+			Some("obj") => Mesh::from_obj(path, &mut self.vertices, &mut self.uvs, &mut self.indices, &mut self.normals, options)?
+				.into_iter().map(|(mesh, material)| (mesh, material, None)).collect(),
+			Some("stl") => Mesh::from_stl(path, &mut self.vertices, &mut self.uvs, &mut self.indices, &mut self.normals, options)?
+				.into_iter().map(|(mesh, color)| (mesh, None, color)).collect(),
+			_ => return Err(format!("unsupported model format for {}, expected .obj or .stl", path.display()))
+		};
+
+		let mut sub_meshes = Vec::with_capacity(parts.len());
+		for (i, (mut mesh, material, color)) in parts.into_iter().enumerate() {
+			let (root, stats) = bvh::build(&self.vertices, &mut self.indices[mesh.indices_start as usize..mesh.indices_end as usize], mesh.indices_start, &mut self.bvh_nodes);
+			mesh.bvh_root = root;
+
+			self.stats.push((format!("{}[{}]", path.display(), i), stats));
+			sub_meshes.push(SubMesh { mesh, material, color });
+		}
+
+		self.ids.entry(key).or_default().push((options, self.files.len()));
+		self.files.push((path.to_path_buf(), sub_meshes.clone()));
+		Ok(sub_meshes)
+	}
+
+	/// Every loaded mesh file and the MTL files the OBJ files can use, including the ones that don't exist yet
+	pub fn files(&self) -> Vec<PathBuf> {
+		let mut files = Vec::new();
+		for (path, _) in self.files.iter() {
+			if files.contains(path) {
+				continue; // Imported again with other options
+			}
+			files.push(path.clone());
+			if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("obj")) {
+				for mtl in mtl_files(path) {
+					if !files.contains(&mtl) {
+						files.push(mtl);
+					}
+				}
+			}
+		}
+		files
+	}
+
+	/// BVH statistics of every loaded sub mesh, labelled with the file and the index of the sub mesh
+	pub fn stats(&self) -> Vec<(String, BvhStats)> {
+		self.stats.clone()
+	}
+}
+
+// MTL files of the mtllib statements of an OBJ file, and the .mtl file with the same name used when there is none
+fn mtl_files(obj: &Path) -> Vec<PathBuf> {
+	let dir = obj.parent().unwrap_or_else(|| Path::new(""));
+	let mut files: Vec<PathBuf> = fs::read_to_string(obj).unwrap_or_default().lines()
+		.filter_map(|l| l.trim().strip_prefix("mtllib"))
+		.flat_map(|names| names.split_whitespace().map(|n| dir.join(n)).collect::<Vec<_>>())
+		.collect();
+	files.push(obj.with_extension("mtl"));
+	files
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn files_are_loaded_once_per_options() {
+		let options = ImportOptions { crease_angle: 0.5, weld_epsilon: 0.0, uv_projection: None };
+		let mut registry = MeshRegistry::new();
+		let first = registry.load("OBJ/cube.obj", options).unwrap();
+		let vertex_count = registry.vertices.len();
+
+		let again = registry.load("OBJ/../OBJ/cube.obj", options).unwrap(); // Same file once canonicalized
+		assert_eq!(registry.vertices.len(), vertex_count);
+		assert_eq!(again.iter().map(|s| s.mesh.indices_start).collect::<Vec<_>>(), first.iter().map(|s| s.mesh.indices_start).collect::<Vec<_>>());
+
+		let creased = registry.load("OBJ/cube.obj", ImportOptions { crease_angle: 0.1, ..options }).unwrap();
+		assert!(registry.vertices.len() > vertex_count);
+		assert!(creased[0].mesh.indices_start >= first.last().unwrap().mesh.indices_end);
+		assert_eq!(registry.files(), vec![PathBuf::from("OBJ/cube.obj"), PathBuf::from("OBJ/cube.mtl")]);
+		assert!(registry.load("OBJ/missing.obj", options).is_err());
+	}
+}
@@ -1,7 +1,7 @@
-use std::ops::Mul;
+use std::ops::{Mul, Neg};
 
 use nalgebra_glm as glm;
-use glm::{Vec3, dot, cross};
+use glm::{Mat3, Vec3, dot, cross};
 
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
@@ -18,6 +18,11 @@ impl Quaternion {
         }
     }
 
+    /// No rotation
+    pub fn identity() -> Self {
+        Self::new(0.0, 0.0, 0.0, 1.0)
+    }
+
     pub fn from_axis(axis: Vec3, a: f32) -> Self { // Expects a normalized axis vector
         let half_a = a / 2.0;
         Self {
@@ -26,15 +31,181 @@ impl Quaternion {
         }
     }
 
+    /// Rotation around the x, y and z axes in radians, applied in z, x, y order like the camera pitch and yaw
+    pub fn from_euler(x: f32, y: f32, z: f32) -> Self {
+        let x = Quaternion::from_axis(Vec3::new(1.0, 0.0, 0.0), x);
+        let y = Quaternion::from_axis(Vec3::new(0.0, 1.0, 0.0), y);
+        let z = Quaternion::from_axis(Vec3::new(0.0, 0.0, 1.0), z);
+        y * x * z
+    }
+
+    /// Angles of `from_euler`, x is in [-pi / 2, pi / 2] and z is 0 when x is at the limits
+    pub fn to_euler(self) -> Vec3 {
+        let m = self.to_matrix();
+        let x = (-m[(1, 2)]).clamp(-1.0, 1.0).asin();
+        if m[(1, 2)].abs() > 0.99999 { // Gimbal lock, y and z turn around the same axis
+            return Vec3::new(x, (-m[(2, 0)]).atan2(m[(0, 0)]), 0.0);
+        }
+        Vec3::new(x, m[(0, 2)].atan2(m[(2, 2)]), m[(1, 0)].atan2(m[(1, 1)]))
+    }
+
+    /// Rotation matrix of a unit quaternion, transforms column vectors like `transform_point`
+    pub fn to_matrix(self) -> Mat3 {
+        let (x, y, z, w) = (self.v.x, self.v.y, self.v.z, self.s);
+        Mat3::new(
+            1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - z * w), 2.0 * (x * z + y * w),
+            2.0 * (x * y + z * w), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - x * w),
+            2.0 * (x * z - y * w), 2.0 * (y * z + x * w), 1.0 - 2.0 * (x * x + y * y)
+        )
+    }
+
+    /// Unit quaternion of a rotation matrix
+    pub fn from_matrix(m: &Mat3) -> Self {
+        let trace = m[(0, 0)] + m[(1, 1)] + m[(2, 2)];
+        // Divides by the largest of the four components, the others can be close to 0
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0; // 4w
+            Self::new((m[(2, 1)] - m[(1, 2)]) / s, (m[(0, 2)] - m[(2, 0)]) / s, (m[(1, 0)] - m[(0, 1)]) / s, s / 4.0)
+        } else if m[(0, 0)] > m[(1, 1)] && m[(0, 0)] > m[(2, 2)] {
+            let s = (1.0 + m[(0, 0)] - m[(1, 1)] - m[(2, 2)]).sqrt() * 2.0; // 4x
+            Self::new(s / 4.0, (m[(0, 1)] + m[(1, 0)]) / s, (m[(0, 2)] + m[(2, 0)]) / s, (m[(2, 1)] - m[(1, 2)]) / s)
+        } else if m[(1, 1)] > m[(2, 2)] {
+            let s = (1.0 + m[(1, 1)] - m[(0, 0)] - m[(2, 2)]).sqrt() * 2.0; // 4y
+            Self::new((m[(0, 1)] + m[(1, 0)]) / s, s / 4.0, (m[(1, 2)] + m[(2, 1)]) / s, (m[(0, 2)] - m[(2, 0)]) / s)
+        } else {
+            let s = (1.0 + m[(2, 2)] - m[(0, 0)] - m[(1, 1)]).sqrt() * 2.0; // 4z
+            Self::new((m[(0, 2)] + m[(2, 0)]) / s, (m[(1, 2)] + m[(2, 1)]) / s, s / 4.0, (m[(1, 0)] - m[(0, 1)]) / s)
+        };
+        q.normalize()
+    }
+
+    /// Orientation of a camera looking along forward, the +z axis is turned to forward and +y towards up
+    pub fn look_at(forward: Vec3, up: Vec3) -> Self {
+        let z = forward.normalize();
+        let x = cross(&up, &z).normalize();
+        let y = cross(&z, &x);
+        Self::from_matrix(&Mat3::from_columns(&[x, y, z]))
+    }
+
+    /// Shortest rotation turning the direction of from to the direction of to
+    pub fn from_to(from: Vec3, to: Vec3) -> Self {
+        let from = from.normalize();
+        let half = from + to.normalize(); // Rotating by twice the angle between from and half gives to
+        if half.norm() < 1e-4 { // Opposite directions, any perpendicular axis works
+            let other = if from.x.abs() < 0.9 { Vec3::new(1.0, 0.0, 0.0) } else { Vec3::new(0.0, 1.0, 0.0) };
+            return Self::from_axis(cross(&from, &other).normalize(), std::f32::consts::PI);
+        }
+        let half = half.normalize();
+        Self { v: cross(&from, &half), s: dot(&from, &half) }
+    }
+
+    pub fn conjugate(&self) -> Self { // Inverse rotation for unit quaternions
+        Self {
+            v: -self.v,
+            s: self.s
+        }
+    }
+
+    /// Inverse of any non zero quaternion, the conjugate is enough for unit quaternions
+    pub fn inverse(&self) -> Self {
+        let norm2 = self.dot(self);
+        Self {
+            v: -self.v / norm2,
+            s: self.s / norm2
+        }
+    }
+
     pub fn transform_point(&self, p: Vec3) -> Vec3 {
-        let inv = Quaternion { v: -self.v, s: self.s };
-        (self * &Quaternion { v: p, s: 0.0 } * inv).v
+        (self * &Quaternion { v: p, s: 0.0 } * self.conjugate()).v
     }
 
     pub fn transform_around(&self, p: Vec3, around: Vec3) -> Vec3 {
         let offset_p = p - around;
         self.transform_point(offset_p) + around
     } 
+
+    pub fn dot(&self, other: &Self) -> f32 {
+        dot(&self.v, &other.v) + self.s * other.s
+    }
+
+    pub fn normalize(&self) -> Self {
+        let norm = self.norm();
+        Self {
+            v: self.v / norm,
+            s: self.s / norm
+        }
+    }
+
+    pub fn norm(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    fn weighted_sum(&self, a: f32, other: &Self, b: f32) -> Self {
+        Self {
+            v: self.v * a + other.v * b,
+            s: self.s * a + other.s * b
+        }
+    }
+
+    /// Rotation going from self at h = 0 to other at h = 1 at a constant angular speed, along the shortest arc
+    pub fn slerp(&self, other: &Self, h: f32) -> Self {
+        let mut cos = self.dot(other);
+        let mut other = *other;
+        if cos < 0.0 { // q and -q are the same rotation, -q is closer
+            other = -other;
+            cos = -cos;
+        }
+
+        if cos > 0.9995 { // Nearly the same rotation, sin(theta) is too small to divide by
+            return self.weighted_sum(1.0 - h, &other, h).normalize();
+        }
+
+        let theta = cos.acos();
+        let sin = theta.sin();
+        self.weighted_sum(((1.0 - h) * theta).sin() / sin, &other, (h * theta).sin() / sin)
+    }
+
+    /// Normalized linear interpolation along the shortest arc, faster than `slerp` but the angular speed isn't constant
+    pub fn nlerp(&self, other: &Self, h: f32) -> Self {
+        let other = if self.dot(other) < 0.0 { -*other } else { *other };
+        self.weighted_sum(1.0 - h, &other, h).normalize()
+    }
+
+    /// Logarithm of a unit quaternion, a pure quaternion holding the axis times half the angle
+    pub fn log(&self) -> Self {
+        let sin = self.v.norm();
+        let half_a = sin.atan2(self.s);
+        Self {
+            v: if sin > 0.0 { self.v * (half_a / sin) } else { Vec3::zeros() },
+            s: 0.0
+        }
+    }
+
+    /// Exponential of a pure quaternion, inverse of `log`
+    pub fn exp(&self) -> Self {
+        let half_a = self.v.norm();
+        Self {
+            v: if half_a > 0.0 { self.v * (half_a.sin() / half_a) } else { Vec3::zeros() },
+            s: half_a.cos()
+        }
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Neg for Quaternion {
+    type Output = Quaternion;
+
+    fn neg(self) -> Self::Output { // Same rotation
+        Quaternion {
+            v: -self.v,
+            s: -self.s
+        }
+    }
 }
 
 impl Mul for &Quaternion {
@@ -59,13 +230,13 @@ impl Mul for Quaternion {
     }
 }
 
-impl Into<[f32; 4]> for Quaternion {
-	fn into(self) -> [f32; 4] {
+impl From<Quaternion> for [f32; 4] {
+	fn from(q: Quaternion) -> Self {
 		[
-			self.v.x,
-			self.v.y,
-			self.v.z,
-			self.s
+			q.v.x,
+			q.v.y,
+			q.v.z,
+			q.s
 		]
 	}
 }
@@ -77,4 +248,165 @@ impl From<[f32; 4]> for Quaternion {
             s: data[3]
         }
     }
-}
\ No newline at end of file
+}
+
+impl From<glm::Quat> for Quaternion {
+    fn from(q: glm::Quat) -> Self {
+        Self::new(q.i, q.j, q.k, q.w)
+    }
+}
+
+impl From<Quaternion> for glm::Quat {
+    fn from(q: Quaternion) -> Self {
+        glm::quat(q.v.x, q.v.y, q.v.z, q.s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    const EPSILON: f32 = 1e-4;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).norm() < EPSILON * (1.0 + a.norm().max(b.norm()))
+    }
+
+    // q and -q are the same rotation
+    fn same_rotation(a: Quaternion, b: Quaternion) -> bool {
+        a.dot(&b).abs() > 1.0 - EPSILON
+    }
+
+    fn vector(scale: f32) -> impl Strategy<Value = Vec3> {
+        (-scale..scale, -scale..scale, -scale..scale).prop_map(|(x, y, z)| Vec3::new(x, y, z))
+    }
+
+    fn direction() -> impl Strategy<Value = Vec3> {
+        vector(1.0).prop_filter("too short to normalize", |v| v.norm() > 0.1).prop_map(|v| v.normalize())
+    }
+
+    fn rotation() -> impl Strategy<Value = Quaternion> {
+        (direction(), -PI..PI).prop_map(|(axis, a)| Quaternion::from_axis(axis, a))
+    }
+
+    proptest! {
+        #[test]
+        fn from_axis_matches_nalgebra(axis in direction(), a in -PI..PI, p in vector(10.0)) {
+            let q = Quaternion::from_axis(axis, a);
+            let expected = glm::quat_rotate_vec3(&glm::quat_angle_axis(a, &axis), &p);
+            prop_assert!(close(q.transform_point(p), expected));
+        }
+
+        #[test]
+        fn composition_applies_the_right_rotation_first(a in rotation(), b in rotation(), p in vector(10.0)) {
+            let composed = (a * b).transform_point(p);
+            prop_assert!(close(composed, a.transform_point(b.transform_point(p))));
+
+            let expected = glm::quat_rotate_vec3(&(glm::Quat::from(a) * glm::Quat::from(b)), &p);
+            prop_assert!(close(composed, expected));
+        }
+
+        #[test]
+        fn inverse_undoes_the_rotation(q in rotation(), scale in 0.1f32..10.0, p in vector(10.0)) {
+            let scaled = Quaternion { v: q.v * scale, s: q.s * scale };
+            prop_assert!(same_rotation((scaled * scaled.inverse()).normalize(), Quaternion::identity()));
+            prop_assert!(((scaled * scaled.inverse()).norm() - 1.0).abs() < EPSILON);
+            prop_assert!(close(q.inverse().transform_point(q.transform_point(p)), p));
+            prop_assert!(close(q.conjugate().transform_point(p), q.inverse().transform_point(p)));
+        }
+
+        #[test]
+        fn rotations_keep_lengths_angles_and_handedness(q in rotation(), a in vector(10.0), b in vector(10.0)) {
+            let (ra, rb) = (q.transform_point(a), q.transform_point(b));
+            prop_assert!((ra.norm() - a.norm()).abs() < EPSILON * (1.0 + a.norm()));
+            prop_assert!((dot(&ra, &rb) - dot(&a, &b)).abs() < EPSILON * (1.0 + a.norm() * b.norm()));
+            prop_assert!(close(cross(&ra, &rb), q.transform_point(cross(&a, &b))));
+        }
+
+        #[test]
+        fn matrix_round_trip(q in rotation(), p in vector(10.0)) {
+            let m = q.to_matrix();
+            prop_assert!(close(m * p, q.transform_point(p)));
+            prop_assert!((m - glm::quat_to_mat3(&glm::Quat::from(q))).norm() < EPSILON);
+            prop_assert!(same_rotation(Quaternion::from_matrix(&m), q));
+        }
+
+        #[test]
+        fn euler_round_trip(x in -FRAC_PI_2 + 0.01..FRAC_PI_2 - 0.01, y in -PI + 0.01..PI - 0.01, z in -PI + 0.01..PI - 0.01) {
+            let angles = Quaternion::from_euler(x, y, z).to_euler();
+            prop_assert!(close(angles, Vec3::new(x, y, z)), "{:?} != {:?}", angles, (x, y, z));
+        }
+
+        #[test]
+        fn euler_of_any_rotation_gives_it_back(q in rotation()) {
+            let e = q.to_euler();
+            prop_assert!(same_rotation(Quaternion::from_euler(e.x, e.y, e.z), q));
+        }
+
+        #[test]
+        fn slerp_turns_at_a_constant_speed(a in rotation(), b in rotation(), h in 0.0f32..1.0) {
+            prop_assert!(same_rotation(a.slerp(&b, 0.0), a));
+            prop_assert!(same_rotation(a.slerp(&b, 1.0), b));
+
+            // Angle of the rotation between two orientations
+            let angle = |p: Quaternion, q: Quaternion| 2.0 * p.dot(&q).abs().min(1.0).acos();
+            let q = a.slerp(&b, h);
+            prop_assert!((angle(a, q) - h * angle(a, b)).abs() < 1e-3);
+
+            let b_near = if a.dot(&b) < 0.0 { -b } else { b }; // nalgebra doesn't pick the shortest arc
+            let expected = glm::quat_slerp(&glm::Quat::from(a), &glm::Quat::from(b_near), h);
+            prop_assert!(same_rotation(q, Quaternion::from(expected)));
+        }
+
+        #[test]
+        fn nlerp_stays_between_the_ends(a in rotation(), b in rotation(), h in 0.0f32..1.0) {
+            let q = a.nlerp(&b, h);
+            prop_assert!((q.norm() - 1.0).abs() < EPSILON);
+            prop_assert!(same_rotation(a.nlerp(&b, 0.0), a));
+            prop_assert!(same_rotation(a.nlerp(&b, 1.0), b));
+        }
+
+        #[test]
+        fn log_and_exp_are_inverses(q in rotation()) {
+            prop_assert!(same_rotation(q.log().exp(), q));
+        }
+
+        #[test]
+        fn look_at_turns_z_to_forward(forward in direction(), up in direction()) {
+            prop_assume!(cross(&forward, &up).norm() > 0.1);
+            let q = Quaternion::look_at(forward, up);
+            prop_assert!(close(q.transform_point(Vec3::new(0.0, 0.0, 1.0)), forward));
+
+            let y = q.transform_point(Vec3::new(0.0, 1.0, 0.0));
+            prop_assert!(dot(&y, &up) > 0.0); // Up is on the top half of the view
+            prop_assert!(dot(&cross(&forward, &up), &y).abs() < EPSILON); // And in the plane of y and z
+        }
+
+        #[test]
+        fn from_to_turns_one_direction_to_the_other(from in direction(), to in direction()) {
+            let q = Quaternion::from_to(from, to);
+            prop_assert!(close(q.transform_point(from), to));
+            prop_assert!((q.norm() - 1.0).abs() < EPSILON);
+        }
+
+        #[test]
+        fn glm_round_trip(q in rotation()) {
+            let back = Quaternion::from(glm::Quat::from(q));
+            prop_assert_eq!(<[f32; 4]>::from(back), <[f32; 4]>::from(q));
+        }
+    }
+
+    #[test]
+    fn from_to_opposite_directions() {
+        let q = Quaternion::from_to(Vec3::new(1.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert!(close(q.transform_point(Vec3::new(1.0, 0.0, 0.0)), Vec3::new(-1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn default_is_identity() {
+        let p = Vec3::new(1.0, 2.0, 3.0);
+        assert!(close(Quaternion::default().transform_point(p), p));
+    }
+}
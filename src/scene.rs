@@ -0,0 +1,627 @@
+// Loads a scene description written in RON into the buffers used by the shader
+// Paths inside a scene file are relative to the scene file itself
+
+use serde::Deserialize;
+use nalgebra_glm::Vec3;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::animation::{Animation, DriverDesc, SceneBuffers, Target, TrackDesc, Property};
+use crate::geom::{sphere::Sphere, model::{ImportOptions, Model}};
+use crate::light::{PointLight, DirectionalLight};
+use crate::camera::{Camera, Lens};
+use crate::bvh::{BvhNode, BvhStats};
+use crate::mesh::MeshRegistry;
+use crate::material::Material;
+use crate::texture::TextureRegistry;
+use crate::quaternion::Quaternion;
+use crate::uv::{Axis, Projection, UvProjection};
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDesc {
+	#[serde(default)]
+	pub camera: CameraDesc,
+	#[serde(default)]
+	pub textures: Vec<PathBuf>, // Indexed by the `texture` fields of the materials, spheres and models
+	#[serde(default)]
+	pub materials: Vec<MaterialDesc>, // Indexed by the `material` field of the spheres and models
+	#[serde(default)]
+	pub spheres: Vec<SphereDesc>,
+	#[serde(default)]
+	pub models: Vec<ModelDesc>,
+	#[serde(default)]
+	pub point_lights: Vec<PointLightDesc>,
+	#[serde(default)]
+	pub directional_lights: Vec<DirectionalLightDesc>,
+	#[serde(default)]
+	pub animations: Vec<TrackDesc>,
+}
+
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDesc {
+	#[serde(default)]
+	pub pos: [f32; 3],
+	#[serde(default)]
+	pub yaw: f32, // Degrees, around the y axis
+	#[serde(default)]
+	pub pitch: f32, // Degrees, around the x axis
+	#[serde(default)]
+	pub aperture: f32, // Radius of the lens, 0 keeps everything in focus
+	#[serde(default = "default_focal_distance")]
+	pub focal_distance: f32,
+	#[serde(default)]
+	pub autofocus: bool, // Focus on the surface at the center of the screen
+	#[serde(default)]
+	pub bokeh: Option<BokehDesc>, // Shape of the lens, a disk by default
+}
+
+impl Default for CameraDesc {
+	fn default() -> Self {
+		Self {
+			pos: [0.0; 3],
+			yaw: 0.0,
+			pitch: 0.0,
+			aperture: 0.0,
+			focal_distance: default_focal_distance(),
+			autofocus: false,
+			bokeh: None
+		}
+	}
+}
+
+/// Polygonal lens, out of focus lights take its shape
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BokehDesc {
+	pub blades: u32, // At least 3
+	#[serde(default)]
+	pub rotation: f32, // Degrees
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SphereDesc {
+	pub pos: [f32; 3],
+	pub r: f32,
+	#[serde(default)]
+	pub material: Option<usize>,
+
+	// Override the material of the sphere, see `MaterialDesc` for the defaults
+	#[serde(default)]
+	pub col: Option<[f32; 4]>,
+	#[serde(default)]
+	pub reflexivity: Option<f32>,
+	#[serde(default)]
+	pub diffuse_factor: Option<f32>,
+	#[serde(default)]
+	pub texture: Option<usize>,
+
+	#[serde(default)]
+	pub rotation: [f32; 3], // Orientation of the texture, see `ModelDesc::rotation`
+	#[serde(default)]
+	pub spin: Option<SpinDesc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelDesc {
+	pub path: PathBuf, // .obj or .stl file
+	#[serde(default)]
+	pub pos: [f32; 3],
+	#[serde(default)]
+	pub rotation: [f32; 3], // Degrees around the x, y and z axes, applied in z, x, y order like the camera pitch and yaw
+	#[serde(default = "default_scale")]
+	pub scale: [f32; 3],
+	#[serde(default)]
+	pub spin: Option<SpinDesc>,
+	#[serde(default)]
+	pub shading: Shading,
+	#[serde(default = "default_crease_angle")]
+	pub crease_angle: f32, // Degrees, faces meeting at a larger angle keep a sharp edge when the file has no normals
+	#[serde(default = "default_weld_epsilon")]
+	pub weld_epsilon: f32, // STL vertices closer than this are merged
+	#[serde(default)]
+	pub uv_projection: Option<UvProjectionDesc>, // Generates the UVs, STL files have none
+	#[serde(default)]
+	pub material: Option<usize>, // Replaces the materials of the MTL file
+
+	// Override the material of the model, the material of the MTL file or the defaults of `MaterialDesc` when it has none
+	#[serde(default)]
+	pub col: Option<[f32; 4]>,
+	#[serde(default)]
+	pub reflexivity: Option<f32>,
+	#[serde(default)]
+	pub diffuse_factor: Option<f32>,
+	#[serde(default)]
+	pub texture: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Shading {
+	Flat, // Normal of the triangle
+	#[default]
+	Smooth, // Vertex normals interpolated across the triangle
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaterialDesc {
+	#[serde(default = "default_col")]
+	pub col: [f32; 4],
+	#[serde(default)]
+	pub texture: Option<usize>,
+	#[serde(default)]
+	pub normal_texture: Option<usize>,
+	#[serde(default = "default_factor")]
+	pub reflexivity: f32,
+	#[serde(default = "default_factor")]
+	pub diffuse_factor: f32,
+	#[serde(default)]
+	pub roughness: f32,
+	#[serde(default)]
+	pub metallic: f32,
+	#[serde(default = "default_ior")]
+	pub ior: f32,
+	#[serde(default)]
+	pub emission: [f32; 3],
+	#[serde(default)]
+	pub absorption: [f32; 3], // Tints transparent materials by the distance travelled inside them
+	#[serde(default = "default_opacity")]
+	pub opacity: f32,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct UvProjectionDesc {
+	pub projection: Projection,
+	#[serde(default = "default_uv_axis")]
+	pub axis: Axis, // Projection direction of Planar, axis of Cylindrical and Spherical, unused by Box
+	#[serde(default = "default_uv_scale")]
+	pub scale: [f32; 2], // Repetitions of the texture per unit, per turn for the angle of Cylindrical and Spherical
+	#[serde(default)]
+	pub offset: [f32; 2],
+	#[serde(default)]
+	pub rotation: f32, // Degrees
+}
+
+impl UvProjectionDesc {
+	fn to_projection(self) -> UvProjection {
+		UvProjection {
+			projection: self.projection,
+			axis: self.axis,
+			scale: self.scale,
+			offset: self.offset,
+			rotation: self.rotation.to_radians()
+		}
+	}
+}
+
+/// Constant rotation applied on top of the rotation of the object, shorthand for a `Spin` track on its `Rotation`
+#[derive(Debug, Copy, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpinDesc {
+	pub axis: [f32; 3], // Normalized when loaded
+	pub speed: f32, // Degrees per second
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PointLightDesc {
+	pub pos: [f32; 3],
+	#[serde(default = "default_light_col")]
+	pub col: [f32; 3],
+	pub intensity: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DirectionalLightDesc {
+	pub dir: [f32; 3], // Normalized when loaded
+	#[serde(default = "default_light_col")]
+	pub col: [f32; 3],
+	#[serde(default = "default_intensity")]
+	pub intensity: f32,
+}
+
+impl SpinDesc {
+	fn to_track(self, target: Target) -> TrackDesc {
+		TrackDesc {
+			target,
+			property: Property::Rotation,
+			driver: DriverDesc::Spin { axis: self.axis, speed: self.speed }
+		}
+	}
+}
+
+impl CameraDesc {
+	/// Push constant matching the described position and orientation, yaw is applied after pitch
+	pub fn to_camera(self) -> Camera {
+		Camera {
+			pos: [self.pos[0], self.pos[1], self.pos[2], 0.0],
+			orientation: Quaternion::from_euler(self.pitch.to_radians(), self.yaw.to_radians(), 0.0).into()
+		}
+	}
+
+	pub fn to_lens(self) -> Lens {
+		Lens {
+			aperture: self.aperture,
+			focal_distance: self.focal_distance,
+			blades: self.bokeh.map_or(0, |b| b.blades),
+			blade_rotation: self.bokeh.map_or(0.0, |b| b.rotation.to_radians()),
+			autofocus: self.autofocus as u32
+		}
+	}
+}
+
+fn default_col() -> [f32; 4] { [1.0, 1.0, 1.0, 1.0] }
+fn default_light_col() -> [f32; 3] { [1.0, 1.0, 1.0] }
+fn default_factor() -> f32 { 0.5 }
+fn default_intensity() -> f32 { 1.0 }
+fn default_scale() -> [f32; 3] { [1.0, 1.0, 1.0] }
+fn default_ior() -> f32 { 1.0 }
+fn default_crease_angle() -> f32 { 60.0 }
+fn default_focal_distance() -> f32 { 10.0 }
+fn default_weld_epsilon() -> f32 { 1e-5 }
+fn default_opacity() -> f32 { 1.0 }
+fn default_uv_axis() -> Axis { Axis::Y }
+fn default_uv_scale() -> [f32; 2] { [1.0, 1.0] }
+
+// Properties an object can set on top of its material
+struct MaterialOverrides {
+	col: Option<[f32; 4]>,
+	reflexivity: Option<f32>,
+	diffuse_factor: Option<f32>,
+	texture_index: Option<i32>,
+}
+
+// Returns the index of the material used by an object, `base_index` is set when `base` is a material declared by the scene
+// Objects overriding properties get their own material, shared with the objects ending up with the same properties
+fn object_material(materials: &mut Vec<Material>, declared: usize, base: Material, base_index: Option<u32>, overrides: MaterialOverrides) -> u32 {
+	let mut m = base;
+	if let Some(col) = overrides.col { m.col = col; }
+	if let Some(r) = overrides.reflexivity { m.reflexivity = r; }
+	if let Some(d) = overrides.diffuse_factor { m.diffuse_factor = d; }
+	if let Some(t) = overrides.texture_index { m.texture_index = t; }
+
+	if let Some(i) = base_index {
+		if m == base {
+			return i;
+		}
+	}
+
+	// Materials declared by the scene are never shared implicitly, so editing one only changes the objects using it
+	match materials[declared..].iter().position(|other| *other == m) {
+		Some(i) => (declared + i) as u32,
+		None => {
+			materials.push(m);
+			(materials.len() - 1) as u32
+		}
+	}
+}
+
+fn rotation_from_degrees(r: [f32; 3]) -> Quaternion {
+	Quaternion::from_euler(r[0].to_radians(), r[1].to_radians(), r[2].to_radians())
+}
+
+/// Scene data ready to be uploaded, every vector maps to one of the shader buffers
+#[derive(Clone)]
+pub struct Scene {
+	pub camera: CameraDesc,
+	pub textures: TextureRegistry,
+	pub materials: Vec<Material>, // Materials declared by the scene first, in the same order
+	pub spheres: Vec<Sphere>,
+	pub models: Vec<Model>,
+	pub vertices: Vec<[f32; 4]>,
+	pub uvs: Vec<[f32; 2]>,
+	pub indices: Vec<[u32; 4]>,
+	pub normals: Vec<[f32; 4]>,
+	pub bvh_nodes: Vec<BvhNode>,
+	pub mesh_stats: Vec<(String, BvhStats)>, // One per unique sub mesh
+	pub point_lights: Vec<PointLight>,
+	pub directional_lights: Vec<DirectionalLight>,
+	pub animation: Animation,
+	pub sources: Vec<PathBuf>, // The scene file and every file it loaded, the scene is loaded again when one of them changes
+}
+
+impl Scene {
+	/// Parses the scene file and loads every model it references, errors are prefixed with the location in the scene file
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+		let path = path.as_ref();
+		let src = fs::read_to_string(path)
+			.map_err(|e| format!("Failed to read scene {}: {}", path.display(), e))?;
+
+		// Options can be written without Some(), so `col: (1.0, 0.0, 0.0, 1.0)` overrides the material of a model
+		let desc: SceneDesc = ron::Options::default()
+			.with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+			.from_str(&src)
+			.map_err(|e| format!("{}:{}", path.display(), e))?;
+
+		let root = path.parent().unwrap_or_else(|| Path::new(""));
+		Self::from_desc(desc, root, path)
+	}
+
+	/// Builds the scene from an already parsed description, `root` is the folder used to resolve relative paths
+	pub fn from_desc(desc: SceneDesc, root: &Path, origin: &Path) -> Result<Self, String> {
+		if desc.camera.aperture < 0.0 {
+			return Err(format!("{}: camera: aperture can't be negative", origin.display()));
+		}
+		if desc.camera.focal_distance <= 0.0 {
+			return Err(format!("{}: camera: focal_distance must be greater than zero", origin.display()));
+		}
+		if desc.camera.bokeh.is_some_and(|b| b.blades < 3) {
+			return Err(format!("{}: camera: bokeh needs at least 3 blades", origin.display()));
+		}
+
+		let mut textures = TextureRegistry::new();
+		let mut declared_textures = Vec::with_capacity(desc.textures.len()); // Registry index of each texture of the scene
+		for (i, t) in desc.textures.iter().enumerate() {
+			let path = root.join(t);
+			if !path.is_file() {
+				return Err(format!("{}: textures[{}]: file {} not found", origin.display(), i, path.display()));
+			}
+			declared_textures.push(textures.add(&path));
+		}
+
+		let texture_index = |texture: Option<usize>, entry: String| -> Result<i32, String> {
+			match texture {
+				None => Ok(-1),
+				Some(i) if i < declared_textures.len() => Ok(declared_textures[i]),
+				Some(i) => Err(format!("{}: {}: texture index {} is out of range, the scene declares {} textures", origin.display(), entry, i, declared_textures.len()))
+			}
+		};
+
+		let mut materials = Vec::with_capacity(desc.materials.len());
+		for (i, m) in desc.materials.iter().enumerate() {
+			let entry = format!("materials[{}]", i);
+			if !(0.0..=1.0).contains(&m.opacity) {
+				return Err(format!("{}: {}: opacity must be between 0 and 1", origin.display(), entry));
+			}
+			if m.ior <= 0.0 {
+				return Err(format!("{}: {}: ior must be positive", origin.display(), entry));
+			}
+
+			materials.push(Material {
+				col: m.col,
+				emission: [m.emission[0], m.emission[1], m.emission[2], 0.0],
+				absorption: [m.absorption[0], m.absorption[1], m.absorption[2], 0.0],
+				texture_index: texture_index(m.texture, entry.clone())?,
+				normal_texture_index: texture_index(m.normal_texture, entry)?,
+				reflexivity: m.reflexivity,
+				diffuse_factor: m.diffuse_factor,
+				roughness: m.roughness,
+				metallic: m.metallic,
+				ior: m.ior,
+				opacity: m.opacity
+			});
+		}
+
+		let declared_materials = materials.len();
+		let declared = materials.clone();
+		let material_ref = |material: Option<usize>, entry: &str| -> Result<Option<(Material, u32)>, String> {
+			match material {
+				None => Ok(None),
+				Some(i) if i < declared_materials => Ok(Some((declared[i], i as u32))),
+				Some(i) => Err(format!("{}: {}: material index {} is out of range, the scene declares {} materials", origin.display(), entry, i, declared_materials))
+			}
+		};
+
+		let mut spheres = Vec::with_capacity(desc.spheres.len());
+		for (i, s) in desc.spheres.iter().enumerate() {
+			let entry = format!("spheres[{}]", i);
+			if s.r <= 0.0 {
+				return Err(format!("{}: {}: r must be greater than zero", origin.display(), entry));
+			}
+			let overrides = MaterialOverrides {
+				col: s.col,
+				reflexivity: s.reflexivity,
+				diffuse_factor: s.diffuse_factor,
+				texture_index: s.texture.map(|_| texture_index(s.texture, entry.clone())).transpose()?
+			};
+
+			let (base, base_index) = match material_ref(s.material, &entry)? {
+				Some((m, i)) => (m, Some(i)),
+				None => (Material::default(), None)
+			};
+			let material_index = object_material(&mut materials, declared_materials, base, base_index, overrides);
+
+			let orientation = rotation_from_degrees(s.rotation);
+			let mut sphere = Sphere::new(s.pos, s.r, material_index);
+			sphere.orientation = orientation.into();
+			spheres.push(sphere);
+		}
+
+		let mut meshes = MeshRegistry::new();
+
+		let mut models = Vec::with_capacity(desc.models.len());
+		let mut model_ranges = Vec::with_capacity(desc.models.len()); // Sub meshes of each model of the file
+		for (i, m) in desc.models.iter().enumerate() {
+			let entry = format!("models[{}]", i);
+			let texture_override = m.texture.map(|_| texture_index(m.texture, entry.clone())).transpose()?;
+			let scene_material = material_ref(m.material, &entry)?;
+			if m.scale.contains(&0.0) {
+				return Err(format!("{}: {}: scale components can't be zero", origin.display(), entry));
+			}
+
+			let model_path = root.join(&m.path);
+			if !model_path.is_file() {
+				return Err(format!("{}: {}: file {} not found", origin.display(), entry, model_path.display()));
+			}
+
+			if m.weld_epsilon < 0.0 {
+				return Err(format!("{}: {}: weld_epsilon can't be negative", origin.display(), entry));
+			}
+
+			let options = ImportOptions {
+				crease_angle: m.crease_angle.to_radians(),
+				weld_epsilon: m.weld_epsilon,
+				uv_projection: m.uv_projection.map(UvProjectionDesc::to_projection)
+			};
+			let sub_meshes = meshes.load(&model_path, options)
+				.map_err(|e| format!("{}: {}: {}", origin.display(), entry, e))?;
+
+			let rotation = rotation_from_degrees(m.rotation);
+			let first = models.len();
+			for sub_mesh in sub_meshes.iter() {
+				// The scene material replaces the MTL material, the fields set on the model override both
+				let (base, base_index) = match (scene_material, sub_mesh.material.as_ref()) {
+					(Some((mat, i)), _) => (mat, Some(i)),
+					(None, Some(mtl)) => {
+						let texture_index = mtl.diffuse_texture.as_ref().map_or(-1, |t| textures.add(t));
+						let normal_texture_index = mtl.bump_texture.as_ref().map_or(-1, |t| textures.add(t));
+						(Material::from_mtl(mtl, texture_index, normal_texture_index), None)
+					},
+					(None, None) => (Material::default(), None)
+				};
+
+
+				let overrides = MaterialOverrides {
+					col: m.col.or_else(|| sub_mesh.color.map(|c| [c[0], c[1], c[2], 1.0])), // STL face colours replace the colour of the material
+					reflexivity: m.reflexivity,
+					diffuse_factor: m.diffuse_factor,
+					texture_index: texture_override
+				};
+				let material_index = object_material(&mut materials, declared_materials, base, base_index, overrides);
+
+				let mut model = Model::new(&sub_mesh.mesh, m.pos, material_index);
+				model.rotation = rotation.into();
+				model.scale = [m.scale[0], m.scale[1], m.scale[2], 0.0];
+				model.smooth_shading = (m.shading == Shading::Smooth) as u32;
+				models.push(model);
+			}
+			model_ranges.push(first..models.len());
+		}
+
+		let mut point_lights: Vec<PointLight> = desc.point_lights.iter()
+			.map(|l| PointLight::new(l.pos.into(), l.col.into(), l.intensity))
+			.collect();
+
+		let mut directional_lights = Vec::with_capacity(desc.directional_lights.len());
+		for (i, l) in desc.directional_lights.iter().enumerate() {
+			let dir = Vec3::from(l.dir);
+			if dir.norm() == 0.0 {
+				return Err(format!("{}: directional_lights[{}]: dir can't be a zero direction", origin.display(), i));
+			}
+			directional_lights.push(DirectionalLight::new(dir.normalize(), l.col.into(), l.intensity));
+		}
+
+		let mut animation = Animation::new(model_ranges, declared_materials);
+		let buffers = SceneBuffers {
+			spheres: &mut spheres,
+			models: &mut models,
+			point_lights: &mut point_lights,
+			directional_lights: &mut directional_lights,
+			materials: &mut materials
+		};
+		// Spins of the objects first, then the tracks of the scene, each track starts from the values loaded above
+		for (i, s) in desc.spheres.iter().enumerate() {
+			if let Some(spin) = s.spin {
+				animation.add(&spin.to_track(Target::Sphere(i)), &buffers, &format!("spheres[{}]", i), origin)?;
+			}
+		}
+		for (i, m) in desc.models.iter().enumerate() {
+			if let Some(spin) = m.spin {
+				animation.add(&spin.to_track(Target::Model(i)), &buffers, &format!("models[{}]", i), origin)?;
+			}
+		}
+		for (i, track) in desc.animations.iter().enumerate() {
+			animation.add(track, &buffers, &format!("animations[{}]", i), origin)?;
+		}
+
+		let mut sources = vec![origin.to_path_buf()];
+		sources.extend(meshes.files());
+		sources.extend(textures.paths().iter().cloned());
+
+		Ok(Self {
+			camera: desc.camera,
+			textures,
+			materials,
+			spheres,
+			models,
+			mesh_stats: meshes.stats(),
+			vertices: meshes.vertices,
+			uvs: meshes.uvs,
+			indices: meshes.indices,
+			normals: meshes.normals,
+			bvh_nodes: meshes.bvh_nodes,
+			point_lights,
+			directional_lights,
+			animation,
+			sources
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+	use std::process;
+
+	// Scene file removed when dropped, even when an assert fails
+	struct TempScene(PathBuf);
+
+	impl TempScene {
+		fn new(name: &str, src: &str) -> Self {
+			let path = env::temp_dir().join(format!("vk_ray3d_scene_test_{}_{}.ron", name, process::id()));
+			fs::write(&path, src).unwrap();
+			Self(path)
+		}
+	}
+
+	impl Drop for TempScene {
+		fn drop(&mut self) {
+			let _ = fs::remove_file(&self.0);
+		}
+	}
+
+	// Error of the scene without the path of the file in front of it
+	fn load_err(name: &str, src: &str) -> String {
+		let file = TempScene::new(name, src);
+		let e = Scene::load(&file.0).err().unwrap_or_else(|| panic!("{} loaded", name));
+		let prefix = format!("{}:", file.0.display());
+		assert!(e.starts_with(&prefix), "{}", e);
+		e[prefix.len()..].trim_start().to_string()
+	}
+
+	#[test]
+	fn rejected_entries_are_located() {
+		assert!(load_err("unknown", "(spheres: [(pos: (0.0, 0.0, 5.0), r: 1.0, radius: 2.0)])").contains("radius"));
+		assert_eq!(load_err("texture", "(spheres: [(pos: (0.0, 0.0, 5.0), r: 1.0, texture: 2)])"),
+			"spheres[0]: texture index 2 is out of range, the scene declares 0 textures");
+		assert_eq!(load_err("material", "(materials: [()], spheres: [(pos: (0.0, 0.0, 5.0), r: 1.0), (pos: (0.0, 0.0, 5.0), r: 1.0, material: 1)])"),
+			"spheres[1]: material index 1 is out of range, the scene declares 1 materials");
+		assert_eq!(load_err("missing_texture", "(textures: [\"missing.png\"])"),
+			format!("textures[0]: file {} not found", env::temp_dir().join("missing.png").display()));
+		assert_eq!(load_err("missing_model", "(models: [(path: \"missing.obj\")])"),
+			format!("models[0]: file {} not found", env::temp_dir().join("missing.obj").display()));
+		assert_eq!(load_err("radius", "(spheres: [(pos: (0.0, 0.0, 5.0), r: 0.0)])"), "spheres[0]: r must be greater than zero");
+		assert_eq!(load_err("dir", "(directional_lights: [(dir: (0.0, 0.0, 0.0))])"), "directional_lights[0]: dir can't be a zero direction");
+		assert_eq!(load_err("opacity", "(materials: [(opacity: 2.0)])"), "materials[0]: opacity must be between 0 and 1");
+	}
+
+	#[test]
+	fn scene_loads() {
+		let cube = env::current_dir().unwrap().join("OBJ/cube.obj");
+		let file = TempScene::new("valid", &format!("(
+			camera: (pos: (0.0, 1.0, 0.0)),
+			materials: [(col: (1.0, 0.0, 0.0, 1.0))],
+			spheres: [(pos: (0.0, 0.0, 5.0), r: 1.0, material: 0), (pos: (2.0, 0.0, 5.0), r: 0.5, col: (0.0, 1.0, 0.0, 1.0))],
+			models: [(path: {:?}, pos: (0.0, 0.0, 8.0)), (path: {:?}, pos: (3.0, 0.0, 8.0), material: 0)],
+			point_lights: [(pos: (0.0, 5.0, 0.0), intensity: 10.0)],
+			directional_lights: [(dir: (0.0, -2.0, 0.0))],
+		)", cube, cube));
+		let scene = Scene::load(&file.0).unwrap();
+
+		assert_eq!(scene.spheres.len(), 2);
+		assert_eq!(scene.spheres[0].material_index, 0);
+		assert_ne!(scene.spheres[1].material_index, 0); // Overriding the colour gives the sphere its own material
+		assert_eq!(scene.models.len(), 2);
+		assert_eq!(scene.models[0].indices_start, scene.models[1].indices_start); // Instances of the same file
+		assert_eq!(scene.directional_lights[0].direction[..3], [0.0, -1.0, 0.0]);
+		assert_eq!(scene.sources[0], file.0);
+		assert!(scene.sources.contains(&cube));
+	}
+}
@@ -0,0 +1,183 @@
+// Reader for ASCII and binary STL files
+// Only the triangles and their colours are read, STL normals are often wrong so they are recomputed from the vertices
+
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy)]
+pub struct StlTriangle {
+	pub vertices: [[f32; 3]; 3],
+	pub color: Option<[f32; 3]>, // From the attribute bytes of binary files
+}
+
+const HEADER_SIZE: usize = 80;
+const TRIANGLE_SIZE: usize = 50; // Normal, 3 vertices and the attribute bytes
+
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<StlTriangle>, String> {
+	let path = path.as_ref();
+	let data = fs::read(path)
+		.map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+
+	// ASCII files start with "solid" but so do some binary files, the size of a binary file is known from its triangle count
+	if is_binary(&data) {
+		Ok(read_binary(&data))
+	} else if data.starts_with(b"solid") {
+		let src = String::from_utf8_lossy(&data);
+		read_ascii(&src).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+	} else {
+		Err(format!("Failed to read {}: neither an ASCII nor a binary STL file", path.display()))
+	}
+}
+
+fn is_binary(data: &[u8]) -> bool {
+	if data.len() < HEADER_SIZE + 4 {
+		return false;
+	}
+	let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+	data.len() == HEADER_SIZE + 4 + count * TRIANGLE_SIZE
+}
+
+fn read_binary(data: &[u8]) -> Vec<StlTriangle> {
+	// Materialise Magics stores a default colour in the header and flags the faces using another colour with a cleared bit 15,
+	// VisCAM and SolidView flag the coloured faces with a set bit 15 and swap red and blue
+	let magics = data[..HEADER_SIZE].windows(6).any(|w| w == b"COLOR=");
+
+	data[HEADER_SIZE + 4..].chunks_exact(TRIANGLE_SIZE).map(|t| {
+		let f32_at = |i: usize| f32::from_le_bytes([t[i], t[i + 1], t[i + 2], t[i + 3]]);
+		let mut vertices = [[0.0; 3]; 3];
+		for (v, vertex) in vertices.iter_mut().enumerate() {
+			for (c, component) in vertex.iter_mut().enumerate() {
+				*component = f32_at(12 + v * 12 + c * 4); // Skip the normal
+			}
+		}
+
+		let attribute = u16::from_le_bytes([t[48], t[49]]);
+		let channel = |shift: u16| ((attribute >> shift) & 0x1f) as f32 / 31.0;
+		let color = match (magics, attribute & 0x8000 != 0) {
+			(true, false) => Some([channel(0), channel(5), channel(10)]),
+			(false, true) => Some([channel(10), channel(5), channel(0)]),
+			_ => None
+		};
+
+		StlTriangle { vertices, color }
+	}).collect()
+}
+
+fn read_ascii(src: &str) -> Result<Vec<StlTriangle>, String> {
+	let mut triangles = Vec::new();
+	let mut corners: Vec<[f32; 3]> = Vec::with_capacity(3);
+
+	for (i, line) in src.lines().enumerate() {
+		let mut tokens = line.split_whitespace();
+		match tokens.next() {
+			Some("outer") => corners.clear(),
+			Some("vertex") => {
+				let mut vertex = [0.0; 3];
+				for c in vertex.iter_mut() {
+					*c = tokens.next()
+						.and_then(|t| t.parse().ok())
+						.ok_or_else(|| format!("line {}: expected 3 coordinates after vertex", i + 1))?;
+				}
+				corners.push(vertex);
+			},
+			Some("endloop") => {
+				if corners.len() != 3 {
+					return Err(format!("line {}: facets must have 3 vertices, found {}", i + 1, corners.len()));
+				}
+				triangles.push(StlTriangle {
+					vertices: [corners[0], corners[1], corners[2]],
+					color: None
+				});
+			},
+			_ => () // solid, facet normal, endfacet and endsolid carry nothing needed
+		}
+	}
+
+	Ok(triangles)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::geom::model::{Mesh, ImportOptions};
+	use std::env;
+	use std::process;
+
+	// Binary STL with the header padded to 80 bytes, each triangle is written with a zero normal
+	fn binary(header: &[u8], triangles: &[([[f32; 3]; 3], u16)]) -> Vec<u8> {
+		let mut data = header.to_vec();
+		data.resize(HEADER_SIZE, b' ');
+		data.extend_from_slice(&(triangles.len() as u32).to_le_bytes());
+		for (vertices, attribute) in triangles {
+			data.extend_from_slice(&[0; 12]);
+			for c in vertices.iter().flatten() {
+				data.extend_from_slice(&c.to_le_bytes());
+			}
+			data.extend_from_slice(&attribute.to_le_bytes());
+		}
+		data
+	}
+
+	const TRIANGLE: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+
+	#[test]
+	fn detects_ascii_and_binary() {
+		let ascii = "solid cube\n facet normal 0 0 1\n  outer loop\n   vertex 0 0 0\n   vertex 1 0 0\n   vertex 0 1 0\n  endloop\n endfacet\nendsolid cube\n";
+		assert!(!is_binary(ascii.as_bytes()));
+		let triangles = read_ascii(ascii).unwrap();
+		assert_eq!(triangles.len(), 1);
+		assert_eq!(triangles[0].vertices, TRIANGLE);
+		assert!(read_ascii("solid\nouter loop\nvertex 0 0 0\nvertex 1 0 0\nendloop\n").is_err());
+
+		let data = binary(b"solid exported as binary", &[(TRIANGLE, 0); 2]); // The size tells it apart from ASCII
+		assert!(is_binary(&data));
+		assert!(!is_binary(&data[..data.len() - 1]));
+		let triangles = read_binary(&data);
+		assert_eq!(triangles.len(), 2);
+		assert_eq!(triangles[1].vertices, TRIANGLE);
+	}
+
+	#[test]
+	fn reads_magics_and_viscam_colours() {
+		let red = 0x1f; // Lowest 5 bits
+		let colors = |header: &[u8]| read_binary(&binary(header, &[(TRIANGLE, red), (TRIANGLE, red | 0x8000), (TRIANGLE, 0)]))
+			.iter().map(|t| t.color).collect::<Vec<_>>();
+
+		// Magics: cleared bit 15 for the faces with their own colour, stored as RGB
+		assert_eq!(colors(b"COLOR=\xff\xff\xff\xff MATERIAL="), vec![Some([1.0, 0.0, 0.0]), None, Some([0.0, 0.0, 0.0])]);
+		// VisCAM: set bit 15 for the coloured faces, stored as BGR
+		assert_eq!(colors(b"binary STL"), vec![None, Some([0.0, 0.0, 1.0]), None]);
+	}
+
+	#[test]
+	fn welds_splits_colours_and_skips_degenerate_faces() {
+		let jitter = 1e-5;
+		let red = 0x8000 | 0x1f << 10; // VisCAM
+		let blue = 0x8000 | 0x1f;
+		let data = binary(b"two colours", &[
+			([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]], red),
+			([[jitter, 0.0, 0.0], [1.0, 1.0 + jitter, 0.0], [0.0, 1.0, 0.0]], red), // Closes the quad, with corners slightly off
+			([[2.0, 0.0, 0.0], [3.0, 0.0, 0.0], [2.0, 1.0, 0.0]], blue),
+			([[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, jitter, 0.0]], red), // Degenerate once welded
+		]);
+		let path = env::temp_dir().join(format!("vk_ray3d_stl_test_{}.stl", process::id()));
+		fs::write(&path, data).unwrap();
+
+		let load = |weld_epsilon: f32| {
+			let (mut vertices, mut uvs, mut indices, mut normals) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+			let options = ImportOptions { crease_angle: 0.5, weld_epsilon, uv_projection: None };
+			let meshes = Mesh::from_stl(&path, &mut vertices, &mut uvs, &mut indices, &mut normals, options).unwrap();
+			assert!(normals.iter().all(|n| *n == [0.0, 0.0, 1.0, 0.0]));
+			meshes.iter().map(|(m, color)| (m.indices_end - m.indices_start, m.vertex_end - m.vertex_start, *color)).collect::<Vec<_>>()
+		};
+		let welded = load(1e-3);
+		let exact = load(0.0);
+		fs::remove_file(&path).unwrap();
+
+		// One mesh per colour in order of appearance, the red quad shares its diagonal once welded
+		assert_eq!(welded, vec![(2, 4, Some([1.0, 0.0, 0.0])), (1, 3, Some([0.0, 0.0, 1.0]))]);
+		// Without welding the last face isn't degenerate and each corner slightly off is its own vertex
+		assert_eq!(exact[0].0, 3);
+		assert_eq!(exact[0].1, 7);
+	}
+}
@@ -1,7 +1,9 @@
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use crate::vulkano;
+use crate::image::RgbaImage;
 
 use vulkano::device::{Device, Queue};
 use vulkano::image::{view::ImageView, immutable::ImmutableImage, ImageDimensions, MipmapsCount};
@@ -9,22 +11,108 @@ use vulkano::sampler::Sampler;
 use vulkano::format::Format;
 use vulkano::sync::GpuFuture;
 
+pub type Texture = (Arc<ImageView<Arc<ImmutableImage<Format>>>>, Arc<Sampler>);
 
-pub fn load_texture<P>(path: P, _device: Arc<Device>, _queue: Arc<Queue>) -> (Arc<ImageView<Arc<ImmutableImage<Format>>>>, Arc<Sampler>) 
-where P: AsRef<Path> {
-	let base_texture_image = crate::image::open(path).unwrap().into_rgba8();
-	let (w, h) = base_texture_image.dimensions();
-	
-	println!("W: {}, H: {}", w, h);
+/// Textures used by a scene, each file is stored once and keeps the index it was given
+#[derive(Debug, Default, Clone)]
+pub struct TextureRegistry {
+	ids: HashMap<PathBuf, usize>, // Canonical path to index in `paths`
+	paths: Vec<PathBuf>,
+}
 
-	let base_texture_data = base_texture_image.as_raw();
+impl TextureRegistry {
+	pub fn new() -> Self {
+		Self::default()
+	}
 
-	let (base_texture, init) = ImmutableImage::from_iter(base_texture_data.iter().cloned(), ImageDimensions::Dim2d { width: w, height: h, array_layers: 1}, MipmapsCount::One, Format::R8G8B8A8Unorm, _queue.clone()).unwrap();
+	/// Returns the index of the texture, the file is only added the first time
+	pub fn add<P: AsRef<Path>>(&mut self, path: P) -> i32 {
+		let path = path.as_ref();
+		let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+		let paths = &mut self.paths;
+		*self.ids.entry(key).or_insert_with(|| {
+			paths.push(path.to_path_buf());
+			paths.len() - 1
+		}) as i32
+	}
+
+	/// Files of the textures, in index order
+	pub fn paths(&self) -> &[PathBuf] {
+		&self.paths
+	}
+
+	/// Size of the textures array in the shader, at least 1 since an array can't be empty
+	pub fn slot_count(&self) -> usize {
+		self.paths.len().max(1)
+	}
+
+	/// Decodes every texture, the ones failing to load are replaced by the fallback texture
+	pub fn load_images(&self) -> Vec<RgbaImage> {
+		self.paths.iter().map(|path| load_image(path).unwrap_or_else(|e| {
+			eprintln!("{}, using the fallback texture", e);
+			fallback_image()
+		})).collect()
+	}
+
+	/// Uploads the textures to the device, fills the single slot of an empty registry with the fallback texture
+	pub fn load_all(&self, _device: Arc<Device>, _queue: Arc<Queue>) -> Vec<Texture> {
+		let mut images = self.load_images();
+		if images.is_empty() {
+			images.push(fallback_image());
+		}
+
+		images.into_iter().map(|img| upload(img, _device.clone(), _queue.clone())).collect()
+	}
+}
+
+pub fn load_image<P: AsRef<Path>>(path: P) -> Result<RgbaImage, String> {
+	let path = path.as_ref();
+	crate::image::open(path)
+		.map(|img| img.into_rgba8())
+		.map_err(|e| format!("Failed to load {}: {}", path.display(), e))
+}
+
+/// Magenta and black checkerboard, stands out on the objects whose texture failed to load
+pub fn fallback_image() -> RgbaImage {
+	RgbaImage::from_fn(2, 2, |x, y| if (x + y) % 2 == 0 {
+		crate::image::Rgba([255, 0, 255, 255])
+	} else {
+		crate::image::Rgba([0, 0, 0, 255])
+	})
+}
+
+fn upload(img: RgbaImage, _device: Arc<Device>, _queue: Arc<Queue>) -> Texture {
+	let (w, h) = img.dimensions();
+
+	let (texture, init) = ImmutableImage::from_iter(img.into_raw().into_iter(), ImageDimensions::Dim2d { width: w, height: h, array_layers: 1 }, MipmapsCount::One, Format::R8G8B8A8Unorm, _queue.clone()).unwrap();
 	init.then_signal_fence_and_flush().unwrap()
 		.wait(None).unwrap();
 
-	let base_texture_view = ImageView::new(base_texture).unwrap();
-	let base_texture_sampler = Sampler::simple_repeat_linear(_device.clone());
+	let texture_view = ImageView::new(texture).unwrap();
+	let texture_sampler = Sampler::simple_repeat_linear(_device.clone());
+
+	(texture_view, texture_sampler)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn files_keep_their_slot() {
+		let mut registry = TextureRegistry::new();
+		assert_eq!(registry.slot_count(), 1); // The fallback texture fills the array of a scene without textures
+
+		assert_eq!(registry.add("Images/grid.jpg"), 0);
+		assert_eq!(registry.add("Images/missing.png"), 1); // Missing files keep a slot, drawn with the fallback texture
+		assert_eq!(registry.add("Images/../Images/grid.jpg"), 0);
+		assert_eq!(registry.add("Images/missing.png"), 1);
+		assert_eq!(registry.slot_count(), 2);
+		assert_eq!(registry.paths(), [PathBuf::from("Images/grid.jpg"), PathBuf::from("Images/missing.png")]);
 
-	(base_texture_view, base_texture_sampler)
-}
\ No newline at end of file
+		let images = registry.load_images();
+		assert_eq!(images[1], fallback_image());
+		assert_ne!(images[0], fallback_image());
+	}
+}
@@ -0,0 +1,189 @@
+// Texture coordinates generated at import time for meshes without usable ones, like STL files
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use nalgebra_glm::{Vec2, Vec3, cross};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Projection {
+	Planar, // Along the axis
+	Box, // Along the main axis of the normal of each triangle
+	Cylindrical, // Around the axis, u makes one turn around the centre of the mesh
+	Spherical, // Around the axis, v goes from one pole to the other
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+	X,
+	Y,
+	Z,
+}
+
+impl Axis {
+	fn index(self) -> usize {
+		match self {
+			Axis::X => 0,
+			Axis::Y => 1,
+			Axis::Z => 2
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvProjection {
+	pub projection: Projection,
+	pub axis: Axis,
+	pub scale: [f32; 2], // Repetitions of the texture per unit, or per turn for the angles
+	pub offset: [f32; 2],
+	pub rotation: f32, // Radians, rotates the texture coordinates before the offset
+}
+
+impl UvProjection {
+	// Coordinates of the point projected along the axis, in the plane of the two other axes
+	fn planar(p: Vec3, axis: usize) -> Vec2 {
+		match axis {
+			0 => Vec2::new(p.z, p.y),
+			1 => Vec2::new(p.x, p.z),
+			_ => Vec2::new(p.x, p.y)
+		}
+	}
+
+	// UVs of the corners of a triangle before the scale, rotation and offset
+	fn project(&self, corners: [Vec3; 3], center: Vec3) -> [Vec2; 3] {
+		let axis = self.axis.index();
+		let mut uvs = match self.projection {
+			Projection::Planar => corners.map(|p| Self::planar(p, axis)),
+			Projection::Box => {
+				let n = cross(&(corners[1] - corners[0]), &(corners[2] - corners[0])).abs();
+				let main_axis = if n.x >= n.y && n.x >= n.z { 0 } else if n.y >= n.z { 1 } else { 2 };
+				corners.map(|p| Self::planar(p, main_axis))
+			},
+			Projection::Cylindrical | Projection::Spherical => corners.map(|p| {
+				let d = p - center;
+				let around = Self::planar(d, axis);
+				let u = around.y.atan2(around.x) / (2.0 * PI) + 0.5;
+				let v = match self.projection {
+					Projection::Cylindrical => d[axis],
+					_ => (d[axis] / d.norm().max(f32::EPSILON)).clamp(-1.0, 1.0).acos() / PI
+				};
+				Vec2::new(u, v)
+			})
+		};
+
+		// Triangles crossing the seam of the angle would stretch the whole texture across them
+		if matches!(self.projection, Projection::Cylindrical | Projection::Spherical) {
+			let max_u = uvs.iter().map(|uv| uv.x).fold(f32::MIN, f32::max);
+			for uv in uvs.iter_mut() {
+				if max_u - uv.x > 0.5 {
+					uv.x += 1.0;
+				}
+			}
+		}
+
+		let (sin, cos) = self.rotation.sin_cos();
+		uvs.map(|uv| {
+			let scaled = uv.component_mul(&Vec2::new(self.scale[0], self.scale[1]));
+			Vec2::new(scaled.x * cos - scaled.y * sin, scaled.x * sin + scaled.y * cos) + Vec2::new(self.offset[0], self.offset[1])
+		})
+	}
+}
+
+/// Replaces the UVs of `triangles`, the duplicated vertices are appended to the buffers so the mesh must be the last one loaded
+/// Vertices shared by triangles getting different UVs, along the seams and the edges of the box projection, are duplicated
+pub fn generate_uvs(vertices: &mut Vec<[f32; 4]>, uvs: &mut Vec<[f32; 2]>, normals: &mut Vec<[f32; 4]>, triangles: &mut [[u32; 4]], projection: &UvProjection) {
+	let position = |v: [f32; 4]| Vec3::new(v[0], v[1], v[2]);
+
+	let mut min = Vec3::repeat(f32::MAX);
+	let mut max = Vec3::repeat(f32::MIN);
+	for t in triangles.iter() {
+		for &v in t[..3].iter() {
+			min = min.inf(&position(vertices[v as usize]));
+			max = max.sup(&position(vertices[v as usize]));
+		}
+	}
+	let center = (min + max) / 2.0;
+
+	let mut assigned: HashMap<u32, Vec<([f32; 2], u32)>> = HashMap::new(); // UVs given to each original vertex, with the vertex holding them
+	for t in triangles.iter_mut() {
+		let corners = [position(vertices[t[0] as usize]), position(vertices[t[1] as usize]), position(vertices[t[2] as usize])];
+		let corner_uvs = projection.project(corners, center);
+
+		for (v, uv) in t[..3].iter_mut().zip(corner_uvs.iter()) {
+			let uv = [uv.x, uv.y];
+			let copies = assigned.entry(*v).or_default();
+			if let Some(&(_, copy)) = copies.iter().find(|(other, _)| other == &uv) {
+				*v = copy;
+			} else if copies.is_empty() {
+				uvs[*v as usize] = uv;
+				copies.push((uv, *v));
+			} else {
+				vertices.push(vertices[*v as usize]);
+				normals.push(normals[*v as usize]);
+				uvs.push(uv);
+				let copy = (vertices.len() - 1) as u32;
+				copies.push((uv, copy));
+				*v = copy;
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn projection(projection: Projection, axis: Axis) -> UvProjection {
+		UvProjection { projection, axis, scale: [1.0, 1.0], offset: [0.0, 0.0], rotation: 0.0 }
+	}
+
+	// Runs `generate_uvs` on the triangles, returns the number of vertices after it and the UVs of each corner
+	fn corner_uvs(mut vertices: Vec<[f32; 4]>, mut triangles: Vec<[u32; 4]>, projection: &UvProjection) -> (usize, Vec<[f32; 2]>) {
+		let mut uvs = vec![[0.0; 2]; vertices.len()];
+		let mut normals = vec![[0.0; 4]; vertices.len()];
+		generate_uvs(&mut vertices, &mut uvs, &mut normals, &mut triangles, projection);
+		assert_eq!((uvs.len(), normals.len()), (vertices.len(), vertices.len()));
+		(vertices.len(), triangles.iter().flat_map(|t| t[..3].to_vec()).map(|v| uvs[v as usize]).collect())
+	}
+
+	#[test]
+	fn cylinder_seam_is_duplicated() {
+		// Band of 8 quads around the y axis, no vertex lies on the seam
+		let slices = 8;
+		let mut vertices = Vec::new();
+		for y in 0..2 {
+			for j in 0..slices {
+				let angle = (j as f32 + 0.5) * 2.0 * PI / slices as f32;
+				vertices.push([angle.cos(), y as f32, angle.sin(), 0.0]);
+			}
+		}
+		let mut triangles = Vec::new();
+		for j in 0..slices {
+			let next = (j + 1) % slices;
+			triangles.push([j, next, slices + next, 0]);
+			triangles.push([j, slices + next, slices + j, 0]);
+		}
+
+		let (vertex_count, uvs) = corner_uvs(vertices, triangles, &projection(Projection::Cylindrical, Axis::Y));
+		assert_eq!(vertex_count, 2 * slices as usize + 2); // The column of vertices after the seam also gets u + 1
+		for t in uvs.chunks(3) {
+			let (min, max) = t.iter().fold((f32::MAX, f32::MIN), |(min, max), uv| (min.min(uv[0]), max.max(uv[0])));
+			assert!(max - min < 0.5, "{:?}", t); // No triangle stretches the texture back across the seam
+		}
+	}
+
+	#[test]
+	fn box_projects_along_the_main_axis() {
+		let vertices = vec![
+			[1.0, 0.0, 0.0, 0.0], [1.0, 1.0, 0.0, 0.0], [1.0, 0.0, 1.0, 0.0], // Facing x
+			[0.0, 2.0, 0.0, 0.0], [0.0, 2.0, 1.0, 0.0], [1.0, 2.0, 0.0, 0.0], // Facing y
+		];
+		let triangles = vec![[0, 1, 2, 0], [3, 4, 5, 0]];
+
+		let (vertex_count, uvs) = corner_uvs(vertices, triangles, &projection(Projection::Box, Axis::Z));
+		assert_eq!(vertex_count, 6);
+		assert_eq!(uvs[..3], [[0.0, 0.0], [0.0, 1.0], [1.0, 0.0]]); // (z, y)
+		assert_eq!(uvs[3..], [[0.0, 0.0], [0.0, 1.0], [1.0, 0.0]]); // (x, z)
+	}
+}